@@ -1,8 +1,9 @@
-use crate::ports::PackageInfo;
+use crate::ports::{InstalledFiles, PackageInfo};
 use core::fmt;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
@@ -11,6 +12,9 @@ use std::time::{SystemTime, UNIX_EPOCH};
 #[derive(Serialize, Deserialize)]
 pub struct CacheData {
     pub data: Vec<PackageInfo>,
+    /// Missing in caches written before the libraries were kept.
+    #[serde(default)]
+    pub libraries: Option<InstalledFiles>,
     timestamp: Option<u64>,
     db_mod_time: Option<u64>,
 }
@@ -58,13 +62,22 @@ pub static CACHE_FILE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
         .expect("Failed to create cache file path")
 });
 
+/// Remembers which version of each package the last notification announced.
+pub static NOTIFIED_FILE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    xdg::BaseDirectories::new()
+        .place_cache_file("scun-notified.json")
+        .expect("Failed to create notification state file path")
+});
+
 pub fn save_cache_to_file(
     cache_path: &Path,
     data: &[PackageInfo],
+    libraries: &InstalledFiles,
     db_mod_time: u64,
 ) -> Result<(), CacheError> {
     let cache_data = CacheData {
         data: data.to_owned(),
+        libraries: Some(libraries.clone()),
         timestamp: Some(current_timestamp()?),
         db_mod_time: Some(db_mod_time),
     };
@@ -81,7 +94,7 @@ fn current_timestamp() -> Result<u64, CacheError> {
 }
 
 pub fn is_cache_valid(cache_data: &CacheData, db_mod_time: u64) -> bool {
-    cache_data.db_mod_time == Some(db_mod_time)
+    cache_data.db_mod_time == Some(db_mod_time) && cache_data.libraries.is_some()
 }
 
 pub fn read_cache_from_file(cache_path: &Path) -> Result<CacheData, CacheError> {
@@ -89,3 +102,23 @@ pub fn read_cache_from_file(cache_path: &Path) -> Result<CacheData, CacheError>
     let reader = BufReader::new(file);
     Ok(serde_json::from_reader(reader)?)
 }
+
+pub fn read_notified_versions(path: &Path) -> Result<HashMap<String, String>, CacheError> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+pub fn save_notified_versions(
+    path: &Path,
+    versions: &HashMap<String, String>,
+) -> Result<(), CacheError> {
+    // Write and rename so a daemon and a manual notify never read a
+    // partially written file
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    serde_json::to_writer(&mut writer, versions)?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,500 @@
+//! Port footprints: the `.footprint` file of a port lists every file its
+//! package installs as tab separated permissions, owner and path, e.g.
+//! `-rwxr-xr-x root/root usr/lib/libz.so.1.3.1`.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FootprintEntry {
+    /// Permissions in `ls -l` notation, e.g. `-rw-r--r--`.
+    pub mode: String,
+    /// `user/group`, by name.
+    pub owner: String,
+    pub path: String,
+    /// The target of a symbolic link.
+    pub target: Option<String>,
+}
+
+pub fn read_footprint(port_dir: &Path) -> io::Result<Vec<FootprintEntry>> {
+    Ok(parse_footprint(&fs::read_to_string(
+        port_dir.join(".footprint"),
+    )?))
+}
+
+/// Parses the tab separated footprint lines; lines without all three fields
+/// are skipped. The ` (EMPTY)` mark of empty files and the `(major, minor)`
+/// numbers of device nodes are not part of the path.
+pub fn parse_footprint(contents: &str) -> Vec<FootprintEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let (mode, owner, path) = (fields.next()?, fields.next()?, fields.next()?);
+            let path = path.strip_suffix(" (EMPTY)").unwrap_or(path);
+            let path = match mode.chars().next() {
+                Some('b' | 'c') => strip_device_numbers(path),
+                _ => path,
+            };
+            let (path, target) = match path.split_once(" -> ") {
+                Some((path, target)) => (path, Some(target.to_string())),
+                None => (path, None),
+            };
+            Some(FootprintEntry {
+                mode: mode.to_string(),
+                owner: owner.to_string(),
+                path: path.to_string(),
+                target,
+            })
+        })
+        .collect()
+}
+
+/// Strips the ` (5, 1)` device numbers footprints append to device nodes.
+fn strip_device_numbers(path: &str) -> &str {
+    let Some((node, numbers)) = path.rsplit_once(" (") else {
+        return path;
+    };
+    let is_device_numbers = numbers.strip_suffix(')').is_some_and(|numbers| {
+        numbers
+            .split(", ")
+            .all(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if is_device_numbers {
+        node
+    } else {
+        path
+    }
+}
+
+/// The placeholder footprints use for the version of the running kernel in
+/// the paths of kernel modules.
+const KERNEL_VERSION: &str = "<kernel-version>";
+
+/// Finds the kernel version an installed path uses where a footprint path
+/// has the `<kernel-version>` placeholder.
+fn installed_kernel_version<'a>(
+    installed: &'a [String],
+    footprint: &[FootprintEntry],
+) -> Option<&'a str> {
+    footprint.iter().find_map(|entry| {
+        let (prefix, suffix) = entry.path.split_once(KERNEL_VERSION)?;
+        installed.iter().find_map(|path| {
+            let version = path.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            (!version.is_empty() && !version.contains('/')).then_some(version)
+        })
+    })
+}
+
+/// A shared library whose soname changes with an update, which breaks the
+/// ABI for every package linking against it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SonameBump {
+    /// The installed library, e.g. `libicuuc.so.74`.
+    pub old: String,
+    /// The library of the new version, e.g. `libicuuc.so.75`.
+    pub new: String,
+}
+
+impl fmt::Display for SonameBump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.old, self.new)
+    }
+}
+
+/// Splits `usr/lib/libfoo.so.1.2.3` into `usr/lib/libfoo.so` and the major
+/// version `1`.
+fn split_soname(path: &str) -> Option<(&str, u64)> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if !file_name.starts_with("lib") {
+        return None;
+    }
+
+    let idx = path.rfind(".so.")?;
+    if idx < path.len() - file_name.len() {
+        return None;
+    }
+    let major = path[idx + 4..].split('.').next()?.parse().ok()?;
+    Some((&path[..idx + 3], major))
+}
+
+/// Whether `path` is a shared library with a major version, the only kind
+/// of installed file soname bumps are looked for in.
+pub fn is_versioned_library(path: &str) -> bool {
+    split_soname(path).is_some()
+}
+
+fn sonames<'a>(paths: impl Iterator<Item = &'a str>) -> BTreeMap<&'a str, BTreeSet<u64>> {
+    let mut sonames: BTreeMap<&str, BTreeSet<u64>> = BTreeMap::new();
+    for (library, major) in paths.filter_map(split_soname) {
+        sonames.entry(library).or_default().insert(major);
+    }
+    sonames
+}
+
+/// Compares the installed files of a package with the footprint of its new
+/// version and returns the libraries whose major version is replaced by
+/// another one.
+pub fn soname_bumps(installed: &[String], footprint: &[FootprintEntry]) -> Vec<SonameBump> {
+    let new_sonames = sonames(footprint.iter().map(|entry| entry.path.as_str()));
+
+    sonames(installed.iter().map(String::as_str))
+        .into_iter()
+        .filter_map(|(library, old_majors)| {
+            let new_majors = new_sonames.get(library)?;
+            let old = old_majors.difference(new_majors).last()?;
+            let new = new_majors.difference(&old_majors).last()?;
+            let name = library.rsplit('/').next().unwrap_or(library);
+            Some(SonameBump {
+                old: format!("{name}.{old}"),
+                new: format!("{name}.{new}"),
+            })
+        })
+        .collect()
+}
+
+/// How installing the new version of a package changes a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    /// A file that is not installed yet.
+    Added(FootprintEntry),
+    /// An installed file the new version no longer contains.
+    Removed(String),
+    /// A file whose permissions, owner or link target differ from the
+    /// `installed` file.
+    Changed {
+        entry: FootprintEntry,
+        installed: FootprintEntry,
+    },
+}
+
+impl FileChange {
+    pub fn path(&self) -> &str {
+        match self {
+            FileChange::Added(entry) | FileChange::Changed { entry, .. } => &entry.path,
+            FileChange::Removed(path) => path,
+        }
+    }
+}
+
+/// Compares the installed files of a package with the footprint of its new
+/// version, sorted by path. `installed_entry` describes an installed file
+/// like a footprint line does; files it cannot find are not compared. The
+/// `<kernel-version>` placeholder stands for the version the installed
+/// kernel modules use.
+pub fn diff_footprint(
+    installed: &[String],
+    footprint: &[FootprintEntry],
+    installed_entry: impl Fn(&str) -> Option<FootprintEntry>,
+) -> Vec<FileChange> {
+    let footprint: Vec<FootprintEntry> = match installed_kernel_version(installed, footprint) {
+        Some(version) => footprint
+            .iter()
+            .map(|entry| FootprintEntry {
+                path: entry.path.replace(KERNEL_VERSION, version),
+                ..entry.clone()
+            })
+            .collect(),
+        None => footprint.to_vec(),
+    };
+    let installed_paths: HashSet<&str> = installed.iter().map(String::as_str).collect();
+    let footprint_paths: HashSet<&str> =
+        footprint.iter().map(|entry| entry.path.as_str()).collect();
+
+    let mut changes: Vec<FileChange> = installed
+        .iter()
+        .filter(|path| !footprint_paths.contains(path.as_str()))
+        .map(|path| FileChange::Removed(path.clone()))
+        .collect();
+    for entry in &footprint {
+        if !installed_paths.contains(entry.path.as_str()) {
+            changes.push(FileChange::Added(entry.clone()));
+        } else if let Some(installed) = installed_entry(&entry.path) {
+            if installed != *entry {
+                changes.push(FileChange::Changed {
+                    entry: entry.clone(),
+                    installed,
+                });
+            }
+        }
+    }
+
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+/// Formats a file mode the way `ls -l` and footprints do, e.g. `drwxr-xr-x`.
+pub fn mode_string(mode: u32) -> String {
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+
+    let mut string = String::from(file_type);
+    // read/write/execute of user, group and other with their special bit
+    for (shift, special, set, unset) in [
+        (6, 0o4000, 's', 'S'),
+        (3, 0o2000, 's', 'S'),
+        (0, 0o1000, 't', 'T'),
+    ] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+/// User and group names by id, read from `/etc/passwd` and `/etc/group`.
+pub struct Owners {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Owners {
+    pub fn read() -> Self {
+        Self {
+            users: read_ids("/etc/passwd"),
+            groups: read_ids("/etc/group"),
+        }
+    }
+
+    /// `user/group` like footprints list them, using the numeric id for
+    /// unknown users and groups.
+    pub fn name(&self, uid: u32, gid: u32) -> String {
+        let name = |names: &HashMap<u32, String>, id: u32| {
+            names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+        };
+        format!("{}/{}", name(&self.users, uid), name(&self.groups, gid))
+    }
+
+    /// The mode, owner and link target of an installed file, given by its
+    /// path relative to `/`.
+    pub fn entry(&self, path: &str) -> Option<FootprintEntry> {
+        let full_path = Path::new("/").join(path);
+        let metadata = fs::symlink_metadata(&full_path).ok()?;
+        let target = if metadata.is_symlink() {
+            Some(
+                fs::read_link(&full_path)
+                    .ok()?
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        } else {
+            None
+        };
+        Some(FootprintEntry {
+            mode: mode_string(metadata.mode()),
+            owner: self.name(metadata.uid(), metadata.gid()),
+            path: path.to_string(),
+            target,
+        })
+    }
+}
+
+/// Reads the `name:password:id:...` lines shared by `/etc/passwd` and
+/// `/etc/group`.
+fn read_ids(path: &str) -> HashMap<u32, String> {
+    parse_ids(&fs::read_to_string(path).unwrap_or_default())
+}
+
+fn parse_ids(contents: &str) -> HashMap<u32, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_footprint_reads_modes_owners_and_links() {
+        assert_eq!(
+            parse_footprint(
+                "drwxr-xr-x\troot/root\tusr/\nlrwxrwxrwx\troot/root\tusr/lib/libz.so -> libz.so.1\nbroken line\n"
+            ),
+            [
+                FootprintEntry {
+                    mode: "drwxr-xr-x".to_string(),
+                    owner: "root/root".to_string(),
+                    path: "usr/".to_string(),
+                    target: None,
+                },
+                FootprintEntry {
+                    mode: "lrwxrwxrwx".to_string(),
+                    owner: "root/root".to_string(),
+                    path: "usr/lib/libz.so".to_string(),
+                    target: Some("libz.so.1".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn soname_bumps_finds_replaced_major_versions() {
+        let installed = paths(&[
+            "usr/lib/libicuuc.so",
+            "usr/lib/libicuuc.so.74",
+            "usr/lib/libicuuc.so.74.2",
+            "usr/lib/libz.so.1",
+            "usr/lib/libz.so.1.3",
+            "usr/lib/libgone.so.2",
+        ]);
+        let footprint = parse_footprint(
+            "\
+lrwxrwxrwx\troot/root\tusr/lib/libicuuc.so -> libicuuc.so.75
+lrwxrwxrwx\troot/root\tusr/lib/libicuuc.so.75 -> libicuuc.so.75.1
+-rwxr-xr-x\troot/root\tusr/lib/libicuuc.so.75.1
+lrwxrwxrwx\troot/root\tusr/lib/libz.so.1 -> libz.so.1.3.1
+-rwxr-xr-x\troot/root\tusr/lib/libz.so.1.3.1
+",
+        );
+
+        assert_eq!(
+            soname_bumps(&installed, &footprint),
+            [SonameBump {
+                old: "libicuuc.so.74".to_string(),
+                new: "libicuuc.so.75".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_footprint_lists_added_removed_and_changed_files() {
+        let installed = paths(&[
+            "usr/",
+            "usr/bin/",
+            "usr/bin/foo",
+            "usr/bin/old",
+            "usr/lib/",
+            "usr/lib/libfoo.so",
+            "usr/lib/libbar.so",
+        ]);
+        let footprint = parse_footprint(
+            "\
+drwxr-xr-x\troot/root\tusr/
+drwxr-xr-x\troot/root\tusr/bin/
+-rwsr-xr-x\troot/wheel\tusr/bin/foo
+-rwxr-xr-x\troot/root\tusr/bin/new
+drwxr-xr-x\troot/root\tusr/lib/
+lrwxrwxrwx\troot/root\tusr/lib/libbar.so -> libbar.so.1
+lrwxrwxrwx\troot/root\tusr/lib/libfoo.so -> libfoo.so.2
+",
+        );
+        let installed_entry = |path: &str| {
+            let (mode, target) = match path {
+                "usr/bin/foo" => ("-rwxr-xr-x", None),
+                "usr/lib/libbar.so" => ("lrwxrwxrwx", Some("libbar.so.1")),
+                "usr/lib/libfoo.so" => ("lrwxrwxrwx", Some("libfoo.so.1")),
+                _ => ("drwxr-xr-x", None),
+            };
+            Some(FootprintEntry {
+                mode: mode.to_string(),
+                owner: "root/root".to_string(),
+                path: path.to_string(),
+                target: target.map(str::to_string),
+            })
+        };
+
+        assert_eq!(
+            diff_footprint(&installed, &footprint, installed_entry),
+            [
+                FileChange::Changed {
+                    entry: footprint[2].clone(),
+                    installed: installed_entry("usr/bin/foo").expect("foo is installed"),
+                },
+                FileChange::Added(footprint[3].clone()),
+                FileChange::Removed("usr/bin/old".to_string()),
+                FileChange::Changed {
+                    entry: footprint[6].clone(),
+                    installed: installed_entry("usr/lib/libfoo.so")
+                        .expect("libfoo.so is installed"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_footprint_matches_empty_files_device_nodes_and_kernel_modules() {
+        let installed = paths(&[
+            "dev/",
+            "dev/console",
+            "etc/",
+            "etc/empty.conf",
+            "lib/modules/6.6.1/",
+            "lib/modules/6.6.1/extra/foo.ko",
+        ]);
+        let footprint = parse_footprint(
+            "\
+drwxr-xr-x\troot/root\tdev/
+crw-------\troot/root\tdev/console (5, 1)
+drwxr-xr-x\troot/root\tetc/
+-rw-r--r--\troot/root\tetc/empty.conf (EMPTY)
+drwxr-xr-x\troot/root\tlib/modules/<kernel-version>/
+-rw-r--r--\troot/root\tlib/modules/<kernel-version>/extra/foo.ko
+",
+        );
+        assert_eq!(footprint[1].path, "dev/console");
+        assert_eq!(footprint[3].path, "etc/empty.conf");
+
+        assert_eq!(diff_footprint(&installed, &footprint, |_| None), []);
+    }
+
+    #[test]
+    fn mode_string_matches_ls() {
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o100644), "-rw-r--r--");
+        assert_eq!(mode_string(0o104755), "-rwsr-xr-x");
+        assert_eq!(mode_string(0o041777), "drwxrwxrwt");
+        assert_eq!(mode_string(0o102640), "-rw-r-S---");
+        assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn owners_fall_back_to_numeric_ids() {
+        let owners = Owners {
+            users: parse_ids("root:x:0:0:root:/root:/bin/sh\nbroken\n"),
+            groups: parse_ids("root:x:0:\nwheel:x:10:root\n"),
+        };
+        assert_eq!(owners.name(0, 10), "root/wheel");
+        assert_eq!(owners.name(1000, 100), "1000/100");
+    }
+
+    #[test]
+    fn split_soname_only_accepts_versioned_libraries() {
+        assert_eq!(
+            split_soname("usr/lib/libssl.so.3"),
+            Some(("usr/lib/libssl.so", 3))
+        );
+        assert_eq!(split_soname("usr/lib/libssl.so"), None);
+        assert_eq!(split_soname("usr/lib/ld-linux.so.2"), None);
+        assert_eq!(split_soname("usr/lib/libfoo.so.1/libbar"), None);
+        assert_eq!(split_soname("usr/lib/libfoo.so.x"), None);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::cache::CacheError;
+use crate::version::{is_release_only, VersionConstraint};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Packages the user does not want to be told about, keyed by package name.
+pub type Holds = BTreeMap<String, Hold>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Last day (`YYYY-MM-DD`) the hold applies; it never expires without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// Version constraint (e.g. `6.6` or `>=6.6,<6.8`) updates have to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow: Option<String>,
+    /// Hold back updates that only bump the release.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_release: bool,
+}
+
+impl Hold {
+    pub fn is_active(&self, today: &str) -> bool {
+        // Zero padded ISO dates order correctly as strings
+        self.until.as_deref().is_none_or(|until| today <= until)
+    }
+
+    /// Whether every update is held, rather than only those outside the
+    /// allowed versions.
+    pub fn is_outright(&self) -> bool {
+        self.allow.is_none() && !self.ignore_release
+    }
+
+    /// Whether updating from `installed` to `available` passes the version
+    /// constraints of the hold.
+    pub fn allows(&self, installed: &str, available: &str) -> Result<bool, String> {
+        if self.ignore_release && is_release_only(installed, available) {
+            return Ok(false);
+        }
+
+        match &self.allow {
+            Some(spec) => Ok(VersionConstraint::parse(spec)?.matches(available)),
+            None => Ok(true),
+        }
+    }
+
+    /// Describes the constraints for tables, `-` for outright holds.
+    pub fn constraint(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(allow) = &self.allow {
+            parts.push(allow.as_str());
+        }
+        if self.ignore_release {
+            parts.push("no release-only");
+        }
+
+        if parts.is_empty() {
+            "-".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+pub static HOLDS_FILE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    xdg::BaseDirectories::with_prefix("scun")
+        .place_config_file("holds.json")
+        .expect("Failed to create hold list path")
+});
+
+pub fn read_holds(path: &Path) -> Result<Holds, CacheError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Holds::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let reader = BufReader::new(file);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+pub fn save_holds(path: &Path, holds: &Holds) -> Result<(), CacheError> {
+    // Write and rename so a watching daemon never reads a partially written
+    // hold list
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    serde_json::to_writer_pretty(&mut writer, holds)?;
+    writer.write_all(b"\n")?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Reads the hold list and keeps only the holds that have not expired.
+pub fn active_holds(path: &Path, today: &str) -> Result<Holds, CacheError> {
+    let mut holds = read_holds(path)?;
+    holds.retain(|_, hold| hold.is_active(today));
+    Ok(holds)
+}
+
+/// Returns `date` if it is a valid `YYYY-MM-DD` date.
+pub fn parse_date(date: &str) -> Option<String> {
+    let mut fields = date.split('-');
+    let (year, month, day) = (fields.next()?, fields.next()?, fields.next()?);
+    if fields.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+
+    let year: u64 = year.parse().ok()?;
+    let month: u64 = month.parse().ok()?;
+    let day: u64 = day.parse().ok()?;
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+
+    (1..=days_in_month).contains(&day).then(|| date.to_string())
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / SECONDS_PER_DAY);
+    date_from_days(days)
+}
+
+/// Converts days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+fn date_from_days(days: u64) -> String {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::tests::TestDir;
+
+    #[test]
+    fn hold_expires_after_its_last_day() {
+        let hold = Hold {
+            until: Some("2024-03-01".to_string()),
+            ..Hold::default()
+        };
+
+        assert!(hold.is_active("2024-02-29"));
+        assert!(hold.is_active("2024-03-01"));
+        assert!(!hold.is_active("2024-03-02"));
+        assert!(Hold::default().is_active("2999-12-31"));
+    }
+
+    #[test]
+    fn pinned_hold_allows_only_matching_updates() {
+        let lts = Hold {
+            allow: Some("6.6".to_string()),
+            ..Hold::default()
+        };
+        let rebuilds = Hold {
+            ignore_release: true,
+            ..Hold::default()
+        };
+
+        assert!(!lts.is_outright());
+        assert_eq!(lts.allows("6.6.1-1", "6.6.2-1"), Ok(true));
+        assert_eq!(lts.allows("6.6.1-1", "6.7-1"), Ok(false));
+        assert_eq!(rebuilds.allows("1.0-1", "1.0-2"), Ok(false));
+        assert_eq!(rebuilds.allows("1.0-1", "1.1-1"), Ok(true));
+        assert_eq!(rebuilds.constraint(), "no release-only");
+        assert!(Hold::default().is_outright());
+    }
+
+    #[test]
+    fn parse_date_accepts_only_valid_iso_dates() {
+        assert_eq!(parse_date("2024-02-29"), Some("2024-02-29".to_string()));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-1-01"), None);
+        assert_eq!(parse_date("tomorrow"), None);
+    }
+
+    #[test]
+    fn date_from_days_converts_unix_days() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(19_782), "2024-02-29");
+        assert_eq!(date_from_days(20_000), "2024-10-04");
+    }
+
+    #[test]
+    fn active_holds_drops_expired_entries() {
+        let temp = TestDir::new("holds");
+        let path = temp.path().join("holds.json");
+        let holds = Holds::from([
+            (
+                "linux".to_string(),
+                Hold {
+                    reason: Some("stay on LTS".to_string()),
+                    ..Hold::default()
+                },
+            ),
+            (
+                "mesa".to_string(),
+                Hold {
+                    until: Some("2024-01-31".to_string()),
+                    ..Hold::default()
+                },
+            ),
+        ]);
+        save_holds(&path, &holds).expect("failed to save holds");
+
+        let active = active_holds(&path, "2024-02-01");
+        assert_eq!(
+            active
+                .expect("failed to read holds")
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec!["linux".to_string()]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Machine-readable output: the `scun print --json` document, the custom
+//! block of `scun print --bar` and the status lines of `scun watch`.
+//!
+//! The `--json` layout is a stable contract for scripts and dashboards:
+//! fields are only ever added, and `format_version` is bumped whenever an
+//! existing field changes its meaning or type.
+
+use crate::footprint::SonameBump;
+use crate::holds::Hold;
+use crate::version::UpdateKind;
+use crate::{MissingDependency, UpdateReport, UpdateRow};
+use serde::Serialize;
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    format_version: u32,
+    total: usize,
+    locked_total: usize,
+    held_total: usize,
+    held_back_total: usize,
+    downgrades_total: usize,
+    updates: Vec<Update<'a>>,
+    locked: Vec<Update<'a>>,
+    held: Vec<Held<'a>>,
+    held_back: Vec<Held<'a>>,
+    downgrades: Vec<Update<'a>>,
+    warnings: &'a [String],
+}
+
+#[derive(Serialize)]
+struct Update<'a> {
+    name: &'a str,
+    installed: &'a str,
+    available: &'a str,
+    kind: UpdateKind,
+    collection: String,
+    collection_name: String,
+    missing_dependencies: Vec<Dependency<'a>>,
+    soname_bumps: &'a [SonameBump],
+}
+
+#[derive(Serialize)]
+struct Dependency<'a> {
+    name: &'a str,
+    collection: Option<String>,
+    collection_name: Option<String>,
+}
+
+impl<'a> From<&'a MissingDependency> for Dependency<'a> {
+    fn from(dependency: &'a MissingDependency) -> Self {
+        Self {
+            name: &dependency.name,
+            collection: dependency
+                .collection
+                .as_ref()
+                .map(|collection| collection.to_string_lossy().into_owned()),
+            collection_name: dependency.collection.as_deref().map(crate::collection_name),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Held<'a> {
+    #[serde(flatten)]
+    update: Update<'a>,
+    reason: Option<&'a str>,
+    until: Option<&'a str>,
+    allow: Option<&'a str>,
+    ignore_release: bool,
+}
+
+impl<'a> From<&'a (UpdateRow, Hold)> for Held<'a> {
+    fn from((row, hold): &'a (UpdateRow, Hold)) -> Self {
+        Self {
+            update: Update::from(row),
+            reason: hold.reason.as_deref(),
+            until: hold.until.as_deref(),
+            allow: hold.allow.as_deref(),
+            ignore_release: hold.ignore_release,
+        }
+    }
+}
+
+impl<'a> From<&'a UpdateRow> for Update<'a> {
+    fn from(row: &'a UpdateRow) -> Self {
+        Self {
+            name: &row.name,
+            installed: &row.installed_version,
+            available: &row.available_version,
+            kind: row.kind(),
+            collection: row.collection.to_string_lossy().into_owned(),
+            collection_name: row.collection_name(),
+            missing_dependencies: row.missing_depends.iter().map(Dependency::from).collect(),
+            soname_bumps: &row.soname_bumps,
+        }
+    }
+}
+
+pub fn format_report(report: &UpdateReport) -> String {
+    let document = Report {
+        format_version: FORMAT_VERSION,
+        total: report.updates.len(),
+        locked_total: report.locked.len(),
+        held_total: report.held.len(),
+        held_back_total: report.held_back.len(),
+        downgrades_total: report.downgrades.len(),
+        updates: report.updates.iter().map(Update::from).collect(),
+        locked: report.locked.iter().map(Update::from).collect(),
+        held: report.held.iter().map(Held::from).collect(),
+        held_back: report.held_back.iter().map(Held::from).collect(),
+        downgrades: report.downgrades.iter().map(Update::from).collect(),
+        warnings: &report.warnings,
+    };
+
+    serde_json::to_string(&document).expect("report only contains serializable strings")
+}
+
+/// Update counts at which the bar block switches to its warning and
+/// critical states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Thresholds {
+    pub warning: usize,
+    pub critical: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            warning: 10,
+            critical: 50,
+        }
+    }
+}
+
+/// A custom block understood by both Waybar (`text`, `tooltip`, `class`) and
+/// i3status-rust (`text`, `state`); each bar ignores the other's fields.
+#[derive(Serialize)]
+struct BarBlock {
+    text: String,
+    tooltip: String,
+    class: &'static str,
+    state: &'static str,
+}
+
+pub fn format_bar_block(report: &UpdateReport, thresholds: Thresholds) -> String {
+    let count = report.updates.len();
+    let (class, state) = match count {
+        0 => ("idle", "Idle"),
+        count if count >= thresholds.critical => ("critical", "Critical"),
+        count if count >= thresholds.warning => ("warning", "Warning"),
+        _ => ("info", "Info"),
+    };
+
+    let tooltip = if report.is_empty() {
+        "No updates available".to_string()
+    } else {
+        crate::format_report(report, false).join("\n")
+    };
+
+    let block = BarBlock {
+        text: format!("󰚰 {count}"),
+        tooltip,
+        class,
+        state,
+    };
+
+    serde_json::to_string(&block).expect("bar block only contains serializable strings")
+}
+
+/// The i3bar protocol header; scun does not handle click events.
+pub const I3BAR_HEADER: &str = r#"{"version":1}"#;
+
+#[derive(Serialize)]
+struct I3barBlock {
+    name: &'static str,
+    full_text: String,
+    short_text: String,
+}
+
+/// Formats one i3bar status line: a JSON array holding the scun block.
+pub fn format_i3bar_status(report: &UpdateReport) -> String {
+    let count = report.updates.len();
+    let status = [I3barBlock {
+        name: "scun",
+        full_text: format!("󰚰 {count}"),
+        short_text: count.to_string(),
+    }];
+
+    serde_json::to_string(&status).expect("status line only contains serializable strings")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn report_with_updates(count: usize) -> UpdateReport {
+        UpdateReport {
+            updates: (0..count)
+                .map(|idx| UpdateRow {
+                    name: format!("port{idx}"),
+                    installed_version: "1.0-1".to_string(),
+                    available_version: "1.1-1".to_string(),
+                    collection: PathBuf::from("/usr/ports/opt"),
+                    ..Default::default()
+                })
+                .collect(),
+            ..UpdateReport::default()
+        }
+    }
+
+    fn bar_block(count: usize) -> serde_json::Value {
+        let thresholds = Thresholds {
+            warning: 2,
+            critical: 3,
+        };
+        serde_json::from_str(&format_bar_block(&report_with_updates(count), thresholds))
+            .expect("bar block is invalid JSON")
+    }
+
+    #[test]
+    fn format_bar_block_switches_state_with_thresholds() {
+        for (count, class, state) in [
+            (0, "idle", "Idle"),
+            (1, "info", "Info"),
+            (2, "warning", "Warning"),
+            (3, "critical", "Critical"),
+        ] {
+            let block = bar_block(count);
+            assert_eq!(block["class"], class);
+            assert_eq!(block["state"], state);
+            assert_eq!(block["text"], format!("󰚰 {count}"));
+        }
+    }
+
+    #[test]
+    fn format_i3bar_status_emits_a_single_block_array() {
+        assert_eq!(
+            format_i3bar_status(&report_with_updates(2)),
+            r#"[{"name":"scun","full_text":"󰚰 2","short_text":"2"}]"#
+        );
+    }
+
+    #[test]
+    fn format_report_lists_missing_dependencies() {
+        let mut report = report_with_updates(1);
+        report.updates[0].missing_depends = vec![
+            MissingDependency {
+                name: "libfoo".to_string(),
+                collection: Some(PathBuf::from("/usr/ports/contrib")),
+            },
+            MissingDependency {
+                name: "gone".to_string(),
+                collection: None,
+            },
+        ];
+
+        let document: serde_json::Value =
+            serde_json::from_str(&format_report(&report)).expect("report is invalid JSON");
+        assert_eq!(
+            document["updates"][0]["missing_dependencies"],
+            serde_json::json!([
+                { "name": "libfoo", "collection": "/usr/ports/contrib", "collection_name": "contrib" },
+                { "name": "gone", "collection": null, "collection_name": null },
+            ])
+        );
+    }
+
+    #[test]
+    fn format_bar_block_puts_update_table_in_tooltip() {
+        assert_eq!(bar_block(0)["tooltip"], "No updates available");
+        assert_eq!(
+            bar_block(1)["tooltip"],
+            "Port  Version Available Kind  Collection\n----- ------- --------- ----- ----------\nport0 1.0-1   1.1-1     minor opt       "
+        );
+    }
+}
@@ -1,37 +1,189 @@
 mod cache;
+mod footprint;
+mod holds;
+mod json;
+mod pkgfile;
+mod plan;
+mod policies;
 mod ports;
+mod version;
+mod watcher;
 
-use libversion::version_compare2;
+use cache::{read_notified_versions, save_notified_versions, NOTIFIED_FILE_PATH};
+use footprint::{FileChange, Owners, SonameBump};
+use holds::{Hold, Holds, HOLDS_FILE_PATH};
+use json::Thresholds;
+use pkgfile::PkgfileError;
+use plan::Dependencies;
+use policies::{Policies, POLICIES_FILE_PATH};
 use ports::*;
 use rayon::prelude::*;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
+use std::time::Duration;
+use version::{Comparison, UpdateKind, VersionConstraint};
+use watcher::Watcher;
 
 const USAGE: &str = "\
-Usage: scun [notify|n|print|p] [OPTION]
+Usage: scun [notify|n|print|p|watch|w|daemon|d] [OPTION]
+       scun hold [PACKAGE [HOLD OPTION]...]
+       scun unhold PACKAGE
+       scun orphans|shadowed|disagreements|plan
+       scun why|impact|diff PACKAGE
 
 Modes:
-  notify, n        Send a desktop notification with available updates
-  print, p         Print the number of available updates
+  notify, n           Send a desktop notification with available updates
+  print, p            Print the number of available updates
+  watch, w            Stream the update count to i3bar/swaybar
+  daemon, d           Recompute updates whenever the pkg db or ports change
+  hold                Hold back updates of a package, or list held packages
+  unhold              Remove a package from the hold list
+  orphans             List installed packages without a port
+  shadowed            List ports hidden by an older port in a collection
+                      listed earlier
+  disagreements       List ports whose version libversion and prt-get order
+                      differently
+  why                 Explain why a package is or is not reported
+  plan                Print the updates in dependency order, followed by a
+                      prt-get update command
+  impact              List the installed packages depending on a package
+  diff                List the files an update of a package adds, removes or
+                      changes the permissions or owner of
+
+Notify options:
+  -a, --all           Show all updates, not only those that are new since
+                      the last notification
 
 Print options:
-  -i, --icon       Print the update count with an icon
-  -l, --long       Print the update table
-  -h, --help       Show this help text
+  -i, --icon          Print the update count with an icon
+  -l, --long          Print the update table
+  -d, --downgrades    Print installed packages that are newer than their port
+  -j, --json          Print updates, locked packages and warnings as JSON
+  -b, --bar           Print a Waybar/i3status-rust custom block as JSON
+  --warning N         Use the warning state from N updates on (default: 10)
+  --critical N        Use the critical state from N updates on (default: 50)
+  --kind LIST         Only show updates of these comma separated kinds:
+                      major, minor, patch or release
+  --repo LIST         Only show updates from these comma separated
+                      collections, by name (e.g. opt) or path
+  --sort KEY          Sort updates by name or by kind instead of pkg db order
+  --dependents        With --long, count the installed packages depending on
+                      each update
+
+Watch options:
+  --interval N        Check for updates every N seconds without inotify (default: 60)
+
+Daemon options:
+  --notify            Send a notification about new updates (default)
+  --status-file PATH  Write the JSON report of print --json to PATH
+
+Hold options:
+  --allow SPEC        Only hold back versions outside SPEC, e.g. 6.6 for any
+                      6.6.x or >=6.6,<6.8 for a range
+  --ignore-release    Only hold back updates that just bump the release
+  --until DATE        Release the hold after DATE (YYYY-MM-DD)
+  --reason TEXT       Remember why the package is held
+
+Options:
+  --prefer-higher     Use the highest version found in any collection
+  --no-prefer-higher  Use the first collection that provides a port
+  --include-locked    Count packages locked in prt-get.locker as updates
+  --compare NAME      Compare versions with NAME: libversion (default),
+                      prt-get, p-is-patch, any-is-patch, string, numeric or
+                      ports; compare.conf can choose one per package
+  -h, --help          Show this help text
+
+Without a --prefer-higher option the preferhigher setting of prt-get.conf is used.
 ";
 
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(60);
+const CHANGE_DEBOUNCE: Duration = Duration::from_secs(2);
+
 #[derive(Debug, PartialEq, Eq)]
 enum CliAction {
-    Run(Command),
+    Run(Command, Options),
     Help,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum Command {
-    Notify,
-    Print(PrintMode),
+    Notify { all: bool },
+    Print(PrintMode, ReportView),
+    Watch(Duration),
+    Daemon(DaemonOutputs),
+    Hold { package: String, hold: Hold },
+    Unhold(String),
+    ListHolds,
+    Orphans,
+    Shadowed,
+    Disagreements,
+    Why(String),
+    Plan,
+    Impact(String),
+    Diff(String),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct DaemonOutputs {
+    notify: bool,
+    status_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Options {
+    prefer_higher: Option<bool>,
+    include_locked: bool,
+    comparison: Comparison,
+}
+
+impl Options {
+    fn prefer_higher(&self) -> bool {
+        self.prefer_higher.unwrap_or_else(prt_get_prefers_higher)
+    }
+}
+
+/// The options together with the collections of prt-get.conf and the
+/// comparison policies of compare.conf, read once per run.
+#[derive(Debug, Default)]
+struct Context {
+    options: Options,
+    repositories: Vec<Repository>,
+    policies: Policies,
+    /// Lines of compare.conf that could not be used, reported with every
+    /// update report.
+    warnings: Vec<String>,
+}
+
+impl Context {
+    fn load(options: Options) -> Self {
+        let (policies, warnings) =
+            policies::read_policies(&POLICIES_FILE_PATH).unwrap_or_else(|e| {
+                let warning = format!("Failed to read {}: {e}", POLICIES_FILE_PATH.display());
+                (Policies::new(), vec![warning])
+            });
+        Self {
+            options,
+            repositories: repositories().to_vec(),
+            policies,
+            warnings,
+        }
+    }
+
+    /// The comparison used for a package: its policy in compare.conf, or
+    /// the one `--compare` selects.
+    fn comparison_for(&self, name: &str) -> Comparison {
+        self.policies
+            .get(name)
+            .copied()
+            .unwrap_or(self.options.comparison)
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -39,13 +191,45 @@ enum PrintMode {
     Count,
     Icon,
     Long,
+    Downgrades,
+    Json,
+    Bar(Thresholds),
+}
+
+/// Which updates `print` shows, and in which order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ReportView {
+    /// Only show updates of these kinds; all kinds if empty.
+    kinds: Vec<UpdateKind>,
+    /// Only show updates from these collections, by name or path; all
+    /// collections if empty.
+    repos: Vec<String>,
+    sort: SortOrder,
+    /// Count the installed packages depending on each update.
+    dependents: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SortOrder {
+    /// Package database order
+    #[default]
+    Installed,
+    Name,
+    /// Most significant change first
+    Kind,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum CliError {
     MissingMode,
     InvalidMode(String),
+    MissingPackage(&'static str),
     InvalidPrintOption(String),
+    MissingValue(&'static str),
+    InvalidValue {
+        option: &'static str,
+        value: String,
+    },
     UnexpectedArgument {
         mode: &'static str,
         argument: String,
@@ -55,16 +239,29 @@ enum CliError {
 impl std::fmt::Display for CliError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CliError::MissingMode => write!(f, "Missing mode. Use 'notify' or 'print'."),
+            CliError::MissingMode => {
+                write!(
+                    f,
+                    "Missing mode. Use 'notify', 'print', 'watch' or 'daemon'."
+                )
+            }
             CliError::InvalidMode(mode) => {
-                write!(f, "Invalid mode: {mode}. Use 'notify' or 'print'.")
+                write!(
+                    f,
+                    "Invalid mode: {mode}. Use 'notify', 'print', 'watch' or 'daemon'."
+                )
             }
             CliError::InvalidPrintOption(option) => {
                 write!(
                     f,
-                    "Invalid print option: {option}. Use '--icon' or '--long'."
+                    "Invalid print option: {option}. Use '--icon', '--long', '--downgrades', '--json' or '--bar'."
                 )
             }
+            CliError::MissingPackage(mode) => write!(f, "Missing package name for {mode}."),
+            CliError::MissingValue(option) => write!(f, "Missing value for {option}."),
+            CliError::InvalidValue { option, value } => {
+                write!(f, "Invalid value for {option}: {value}.")
+            }
             CliError::UnexpectedArgument { mode, argument } => {
                 write!(f, "Unexpected argument for {mode}: {argument}.")
             }
@@ -72,18 +269,194 @@ impl std::fmt::Display for CliError {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 struct UpdateRow {
     name: String,
     installed_version: String,
     available_version: String,
+    collection: PathBuf,
+    /// Dependencies of the available version that are not installed.
+    missing_depends: Vec<MissingDependency>,
+    /// How many installed packages depend on the package, directly or
+    /// indirectly; only counted for `print --long --dependents`.
+    dependents: Option<usize>,
+    /// Shared libraries whose soname changes with the update.
+    soname_bumps: Vec<SonameBump>,
+}
+
+impl UpdateRow {
+    fn kind(&self) -> UpdateKind {
+        UpdateKind::classify(&self.installed_version, &self.available_version)
+    }
+
+    fn collection_name(&self) -> String {
+        collection_name(&self.collection)
+    }
+}
+
+/// A dependency of a port that no installed package satisfies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MissingDependency {
+    name: String,
+    /// The collection providing a port for it, if any.
+    collection: Option<PathBuf>,
+}
+
+impl std::fmt::Display for MissingDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.collection {
+            Some(collection) => write!(f, "{} ({})", self.name, collection_name(collection)),
+            None => write!(f, "{} (no port)", self.name),
+        }
+    }
+}
+
+/// The name of a collection, e.g. `opt` for `/usr/ports/opt`.
+fn collection_name(collection: &Path) -> String {
+    collection
+        .file_name()
+        .unwrap_or(collection.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PackageUpdate {
+    Available(UpdateRow),
+    /// The available version is outside the versions the package's hold allows.
+    HeldBack(UpdateRow, Hold),
+    /// The installed version is newer than the port, e.g. after a local
+    /// build or when a collection rolled the port back.
+    Downgrade(UpdateRow),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct UpdateReport {
+    updates: Vec<UpdateRow>,
+    locked: Vec<UpdateRow>,
+    held: Vec<(UpdateRow, Hold)>,
+    held_back: Vec<(UpdateRow, Hold)>,
+    downgrades: Vec<UpdateRow>,
+    warnings: Vec<String>,
+}
+
+impl UpdateReport {
+    fn new(packages: Vec<PackageUpdate>, locked_packages: &HashSet<String>, holds: &Holds) -> Self {
+        let mut report = Self::default();
+
+        for package in packages {
+            match package {
+                PackageUpdate::Downgrade(row) => report.downgrades.push(row),
+                PackageUpdate::Available(row) | PackageUpdate::HeldBack(row, _)
+                    if locked_packages.contains(&row.name) =>
+                {
+                    report.locked.push(row);
+                }
+                PackageUpdate::HeldBack(row, hold) => report.held_back.push((row, hold)),
+                PackageUpdate::Available(row) => match holds.get(&row.name) {
+                    Some(hold) if hold.is_outright() => report.held.push((row, hold.clone())),
+                    _ => report.updates.push(row),
+                },
+            }
+        }
+
+        report
+    }
+
+    /// The rows of every section.
+    fn rows_mut(&mut self) -> impl Iterator<Item = &mut UpdateRow> {
+        let held = self.held.iter_mut().chain(&mut self.held_back);
+        self.updates
+            .iter_mut()
+            .chain(&mut self.locked)
+            .chain(held.map(|(row, _)| row))
+            .chain(&mut self.downgrades)
+    }
+
+    /// Whether no section lists any package.
+    fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+            && self.locked.is_empty()
+            && self.held.is_empty()
+            && self.held_back.is_empty()
+            && self.downgrades.is_empty()
+    }
+
+    fn apply_view(&mut self, view: &ReportView) {
+        let shown = |row: &UpdateRow| {
+            (view.kinds.is_empty() || view.kinds.contains(&row.kind()))
+                && (view.repos.is_empty()
+                    || view.repos.iter().any(|repo| {
+                        *repo == row.collection_name() || Path::new(repo) == row.collection
+                    }))
+        };
+        self.updates.retain(shown);
+        self.locked.retain(shown);
+        self.held.retain(|(row, _)| shown(row));
+        self.held_back.retain(|(row, _)| shown(row));
+        self.downgrades.retain(shown);
+
+        let sort = |rows: &mut Vec<UpdateRow>| match view.sort {
+            SortOrder::Installed => {}
+            SortOrder::Name => rows.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortOrder::Kind => rows.sort_by_key(UpdateRow::kind),
+        };
+        let sort_held = |rows: &mut Vec<(UpdateRow, Hold)>| match view.sort {
+            SortOrder::Installed => {}
+            SortOrder::Name => rows.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name)),
+            SortOrder::Kind => rows.sort_by_key(|(row, _)| row.kind()),
+        };
+        sort(&mut self.updates);
+        sort(&mut self.locked);
+        sort_held(&mut self.held);
+        sort_held(&mut self.held_back);
+        sort(&mut self.downgrades);
+    }
 }
 
 fn is_help_arg(arg: &str) -> bool {
     matches!(arg, "-h" | "--help" | "help")
 }
 
+fn parse_global_option<'a, I>(
+    arg: &str,
+    values: &mut I,
+    options: &mut Options,
+) -> Result<bool, CliError>
+where
+    I: Iterator<Item = &'a String>,
+{
+    match arg {
+        "--prefer-higher" => options.prefer_higher = Some(true),
+        "--no-prefer-higher" => options.prefer_higher = Some(false),
+        "--include-locked" => options.include_locked = true,
+        "--compare" => {
+            let value = values.next().ok_or(CliError::MissingValue("--compare"))?;
+            options.comparison = value.parse().map_err(|_| CliError::InvalidValue {
+                option: "--compare",
+                value: value.clone(),
+            })?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
 fn parse_args(args: &[String]) -> Result<CliAction, CliError> {
+    // Options given before the mode apply to every mode; the modes parse
+    // those given after it along with their own, so option values are never
+    // taken for options
+    let mut options = Options::default();
+    let mut args_iter = args.iter();
+    let mut rest = args_iter.as_slice();
+    while let Some(arg) = args_iter.next() {
+        if !parse_global_option(arg, &mut args_iter, &mut options)? {
+            break;
+        }
+        rest = args_iter.as_slice();
+    }
+    let args = rest;
+
     let Some(mode) = args.first().map(String::as_str) else {
         return Err(CliError::MissingMode);
     };
@@ -93,48 +466,333 @@ fn parse_args(args: &[String]) -> Result<CliAction, CliError> {
     }
 
     match mode {
-        "notify" | "n" => {
-            if let Some(argument) = args.get(1) {
-                if is_help_arg(argument) {
-                    Ok(CliAction::Help)
-                } else {
-                    Err(CliError::UnexpectedArgument {
-                        mode: "notify",
-                        argument: argument.clone(),
-                    })
-                }
-            } else {
-                Ok(CliAction::Run(Command::Notify))
-            }
+        "notify" | "n" => parse_notify_args(&args[1..], options),
+        "print" | "p" => parse_print_args(&args[1..], options),
+        "watch" | "w" => parse_watch_args(&args[1..], options),
+        "daemon" | "d" => parse_daemon_args(&args[1..], options),
+        "hold" => parse_hold_args(&args[1..], options),
+        "unhold" => parse_package_arg("unhold", Command::Unhold, &args[1..], options),
+        "why" => parse_package_arg("why", Command::Why, &args[1..], options),
+        "orphans" => parse_no_args("orphans", Command::Orphans, &args[1..], options),
+        "shadowed" => parse_no_args("shadowed", Command::Shadowed, &args[1..], options),
+        "disagreements" => {
+            parse_no_args("disagreements", Command::Disagreements, &args[1..], options)
         }
-        "print" | "p" => parse_print_args(&args[1..]),
+        "plan" => parse_no_args("plan", Command::Plan, &args[1..], options),
+        "impact" => parse_package_arg("impact", Command::Impact, &args[1..], options),
+        "diff" => parse_package_arg("diff", Command::Diff, &args[1..], options),
         _ => Err(CliError::InvalidMode(mode.to_string())),
     }
 }
 
-fn parse_print_args(args: &[String]) -> Result<CliAction, CliError> {
-    let Some(option) = args.first().map(String::as_str) else {
-        return Ok(CliAction::Run(Command::Print(PrintMode::Count)));
-    };
+fn parse_notify_args(args: &[String], mut options: Options) -> Result<CliAction, CliError> {
+    let mut all = false;
+    let mut args = args.iter();
 
-    if is_help_arg(option) {
-        return Ok(CliAction::Help);
+    while let Some(option) = args.next().map(String::as_str) {
+        if parse_global_option(option, &mut args, &mut options)? {
+            continue;
+        }
+        match option {
+            _ if is_help_arg(option) => return Ok(CliAction::Help),
+            "-a" | "--all" => all = true,
+            _ => {
+                return Err(CliError::UnexpectedArgument {
+                    mode: "notify",
+                    argument: option.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(CliAction::Run(Command::Notify { all }, options))
+}
+
+fn parse_print_args(args: &[String], mut options: Options) -> Result<CliAction, CliError> {
+    let mut mode = None;
+    let mut thresholds = None;
+    let mut view = ReportView::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next().map(String::as_str) {
+        if is_help_arg(option) {
+            return Ok(CliAction::Help);
+        }
+        if parse_global_option(option, &mut args, &mut options)? {
+            continue;
+        }
+
+        let selected = match option {
+            "-i" | "--icon" => PrintMode::Icon,
+            "-l" | "--long" => PrintMode::Long,
+            "-d" | "--downgrades" => PrintMode::Downgrades,
+            "-j" | "--json" => PrintMode::Json,
+            "-b" | "--bar" => PrintMode::Bar(Thresholds::default()),
+            "--warning" => {
+                thresholds.get_or_insert_with(Thresholds::default).warning =
+                    parse_count_value("--warning", args.next())?;
+                continue;
+            }
+            "--critical" => {
+                thresholds.get_or_insert_with(Thresholds::default).critical =
+                    parse_count_value("--critical", args.next())?;
+                continue;
+            }
+            "--kind" => {
+                let value = args.next().ok_or(CliError::MissingValue("--kind"))?;
+                view.kinds = value
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| CliError::InvalidValue {
+                        option: "--kind",
+                        value: value.clone(),
+                    })?;
+                continue;
+            }
+            "--repo" => {
+                let value = args.next().ok_or(CliError::MissingValue("--repo"))?;
+                view.repos = value.split(',').map(String::from).collect();
+                continue;
+            }
+            "--dependents" => {
+                view.dependents = true;
+                continue;
+            }
+            "--sort" => {
+                let value = args.next().ok_or(CliError::MissingValue("--sort"))?;
+                view.sort = match value.as_str() {
+                    "name" => SortOrder::Name,
+                    "kind" => SortOrder::Kind,
+                    _ => {
+                        return Err(CliError::InvalidValue {
+                            option: "--sort",
+                            value: value.clone(),
+                        })
+                    }
+                };
+                continue;
+            }
+            _ if option.starts_with('-') && mode.is_none() => {
+                return Err(CliError::InvalidPrintOption(option.to_string()))
+            }
+            _ => return Err(unexpected_print_argument(option)),
+        };
+
+        if mode.replace(selected).is_some() {
+            return Err(unexpected_print_argument(option));
+        }
     }
 
-    let mode = match option {
-        "-i" | "--icon" => PrintMode::Icon,
-        "-l" | "--long" => PrintMode::Long,
-        _ => return Err(CliError::InvalidPrintOption(option.to_string())),
+    let mode = match (mode.unwrap_or(PrintMode::Count), thresholds) {
+        (PrintMode::Bar(_), Some(thresholds)) if thresholds.warning > thresholds.critical => {
+            return Err(CliError::InvalidValue {
+                option: "--warning",
+                value: format!(
+                    "{} is above --critical {}",
+                    thresholds.warning, thresholds.critical
+                ),
+            })
+        }
+        (PrintMode::Bar(_), Some(thresholds)) => PrintMode::Bar(thresholds),
+        (_, Some(_)) => return Err(unexpected_print_argument("--warning/--critical")),
+        (mode, None) => mode,
     };
+    if view.dependents && mode != PrintMode::Long {
+        return Err(unexpected_print_argument("--dependents"));
+    }
 
-    if let Some(argument) = args.get(1) {
-        return Err(CliError::UnexpectedArgument {
-            mode: "print",
-            argument: argument.clone(),
-        });
+    Ok(CliAction::Run(Command::Print(mode, view), options))
+}
+
+fn parse_watch_args(args: &[String], mut options: Options) -> Result<CliAction, CliError> {
+    let mut interval = DEFAULT_WATCH_INTERVAL;
+    let mut args = args.iter();
+
+    while let Some(option) = args.next().map(String::as_str) {
+        if parse_global_option(option, &mut args, &mut options)? {
+            continue;
+        }
+        match option {
+            _ if is_help_arg(option) => return Ok(CliAction::Help),
+            "--interval" => {
+                let value = args.next();
+                match parse_count_value("--interval", value)? {
+                    0 => {
+                        return Err(CliError::InvalidValue {
+                            option: "--interval",
+                            value: "0".to_string(),
+                        })
+                    }
+                    seconds => interval = Duration::from_secs(seconds as u64),
+                }
+            }
+            _ => {
+                return Err(CliError::UnexpectedArgument {
+                    mode: "watch",
+                    argument: option.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(CliAction::Run(Command::Watch(interval), options))
+}
+
+fn parse_daemon_args(args: &[String], mut options: Options) -> Result<CliAction, CliError> {
+    let mut outputs = DaemonOutputs::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next().map(String::as_str) {
+        if parse_global_option(option, &mut args, &mut options)? {
+            continue;
+        }
+        match option {
+            _ if is_help_arg(option) => return Ok(CliAction::Help),
+            "--notify" => outputs.notify = true,
+            "--status-file" => {
+                let path = args.next().ok_or(CliError::MissingValue("--status-file"))?;
+                outputs.status_file = Some(PathBuf::from(path));
+            }
+            _ => {
+                return Err(CliError::UnexpectedArgument {
+                    mode: "daemon",
+                    argument: option.to_string(),
+                })
+            }
+        }
+    }
+
+    if outputs.status_file.is_none() {
+        outputs.notify = true;
+    }
+
+    Ok(CliAction::Run(Command::Daemon(outputs), options))
+}
+
+fn parse_hold_args(args: &[String], mut options: Options) -> Result<CliAction, CliError> {
+    let mut package = None;
+    let mut hold = Hold::default();
+    let mut args = args.iter();
+
+    while let Some(argument) = args.next().map(String::as_str) {
+        if parse_global_option(argument, &mut args, &mut options)? {
+            continue;
+        }
+        match argument {
+            _ if is_help_arg(argument) => return Ok(CliAction::Help),
+            "--until" => {
+                let value = args.next().ok_or(CliError::MissingValue("--until"))?;
+                hold.until =
+                    Some(
+                        holds::parse_date(value).ok_or_else(|| CliError::InvalidValue {
+                            option: "--until",
+                            value: value.clone(),
+                        })?,
+                    );
+            }
+            "--reason" => {
+                let value = args.next().ok_or(CliError::MissingValue("--reason"))?;
+                hold.reason = Some(value.clone());
+            }
+            "--allow" => {
+                let value = args.next().ok_or(CliError::MissingValue("--allow"))?;
+                if VersionConstraint::parse(value).is_err() {
+                    return Err(CliError::InvalidValue {
+                        option: "--allow",
+                        value: value.clone(),
+                    });
+                }
+                hold.allow = Some(value.clone());
+            }
+            "--ignore-release" => hold.ignore_release = true,
+            _ if package.is_none() && !argument.starts_with('-') => {
+                package = Some(argument.to_string());
+            }
+            _ => {
+                return Err(CliError::UnexpectedArgument {
+                    mode: "hold",
+                    argument: argument.to_string(),
+                })
+            }
+        }
+    }
+
+    match package {
+        Some(package) => Ok(CliAction::Run(Command::Hold { package, hold }, options)),
+        None if hold == Hold::default() => Ok(CliAction::Run(Command::ListHolds, options)),
+        None => Err(CliError::MissingPackage("hold")),
+    }
+}
+
+/// Parses the arguments of modes that take a single package name.
+fn parse_package_arg(
+    mode: &'static str,
+    command: fn(String) -> Command,
+    args: &[String],
+    mut options: Options,
+) -> Result<CliAction, CliError> {
+    let mut package = None;
+    let mut args = args.iter();
+
+    while let Some(argument) = args.next() {
+        if is_help_arg(argument) {
+            return Ok(CliAction::Help);
+        }
+        if parse_global_option(argument, &mut args, &mut options)? {
+            continue;
+        }
+        if package.replace(argument.clone()).is_some() {
+            return Err(CliError::UnexpectedArgument {
+                mode,
+                argument: argument.clone(),
+            });
+        }
+    }
+
+    match package {
+        Some(package) => Ok(CliAction::Run(command(package), options)),
+        None => Err(CliError::MissingPackage(mode)),
+    }
+}
+
+/// Parses the arguments of modes without options of their own.
+fn parse_no_args(
+    mode: &'static str,
+    command: Command,
+    args: &[String],
+    mut options: Options,
+) -> Result<CliAction, CliError> {
+    let mut args = args.iter();
+
+    while let Some(argument) = args.next() {
+        if is_help_arg(argument) {
+            return Ok(CliAction::Help);
+        }
+        if !parse_global_option(argument, &mut args, &mut options)? {
+            return Err(CliError::UnexpectedArgument {
+                mode,
+                argument: argument.clone(),
+            });
+        }
+    }
+
+    Ok(CliAction::Run(command, options))
+}
+
+fn unexpected_print_argument(argument: &str) -> CliError {
+    CliError::UnexpectedArgument {
+        mode: "print",
+        argument: argument.to_string(),
     }
+}
 
-    Ok(CliAction::Run(Command::Print(mode)))
+fn parse_count_value(option: &'static str, value: Option<&String>) -> Result<usize, CliError> {
+    let value = value.ok_or(CliError::MissingValue(option))?;
+    value.parse().map_err(|_| CliError::InvalidValue {
+        option,
+        value: value.clone(),
+    })
 }
 
 fn configure_rayon_threads() {
@@ -155,180 +813,2228 @@ fn configure_rayon_threads() {
     }
 }
 
-fn update_for_package((name, version): &PackageInfo) -> Option<UpdateRow> {
-    let port_dir = find_ports_in_repositories(name)?;
-    let available_version = extract_pkgfile_version(&port_dir)?;
+/// Finds the port of an installed package, falling back to the port it is
+/// an alias for.
+fn find_port(name: &str, context: &Context) -> Option<PathBuf> {
+    let prefer_higher = context.options.prefer_higher();
+    let comparison = context.comparison_for(name);
+    let find =
+        |port| find_port_in_repositories(port, &context.repositories, prefer_higher, comparison);
+    find(name).or_else(|| aliased_port(name).and_then(find))
+}
+
+/// Maps the names a dependency can be satisfied by to the installed package
+/// satisfying it: every installed package provides itself and the port it
+/// is an alias for.
+type InstalledProviders<'a> = HashMap<&'a str, &'a str>;
+
+fn installed_providers(packages: &[PackageInfo]) -> InstalledProviders<'_> {
+    let mut installed: InstalledProviders = packages
+        .iter()
+        .map(|(name, _)| (name.as_str(), name.as_str()))
+        .collect();
+    for (name, _) in packages {
+        if let Some(port) = aliased_port(name) {
+            installed.entry(port).or_insert(name);
+        }
+    }
+    installed
+}
+
+/// The dependencies of a port that are not installed, with the collection
+/// that provides each of them.
+fn missing_depends(
+    depends: &[String],
+    installed: &InstalledProviders,
+    context: &Context,
+) -> Vec<MissingDependency> {
+    depends
+        .iter()
+        .filter(|name| !installed.contains_key(name.as_str()))
+        .map(|name| MissingDependency {
+            name: name.clone(),
+            collection: find_port(name, context)
+                .and_then(|port_dir| port_dir.parent().map(PathBuf::from)),
+        })
+        .collect()
+}
+
+fn update_for_package(
+    (name, version): &PackageInfo,
+    context: &Context,
+    holds: &Holds,
+    installed: &InstalledProviders,
+    libraries: &InstalledFiles,
+) -> Result<Option<PackageUpdate>, String> {
+    let Some(port_dir) = find_port(name, context) else {
+        return Ok(None);
+    };
+
+    let pkgfile = read_port_pkgfile(&port_dir)
+        .map_err(|e| format!("{}: {e}", port_dir.join("Pkgfile").display()))?;
+    let available_version = pkgfile.version_release();
     let installed_version = version.as_deref().unwrap_or("unknown");
 
-    (version_compare2(&available_version, installed_version) == Ordering::Greater).then(|| {
-        UpdateRow {
-            name: name.to_string(),
-            installed_version: installed_version.to_string(),
-            available_version,
+    // Versions the comparison cannot order are left alone, like prt-get does
+    let comparison = context.comparison_for(name);
+    let comparison = match comparison.compare(&available_version, installed_version) {
+        Some(Ordering::Equal) | None => return Ok(None),
+        Some(comparison) => comparison,
+    };
+
+    let mut row = UpdateRow {
+        name: name.to_string(),
+        installed_version: installed_version.to_string(),
+        available_version,
+        collection: port_dir.parent().map(PathBuf::from).unwrap_or_default(),
+        ..Default::default()
+    };
+
+    if comparison == Ordering::Less {
+        return Ok(Some(PackageUpdate::Downgrade(row)));
+    }
+    row.missing_depends = missing_depends(&pkgfile.depends, installed, context);
+    row.soname_bumps = libraries
+        .get(name)
+        .map(|libraries| soname_bumps(libraries, &port_dir))
+        .unwrap_or_default();
+
+    match holds.get(name).filter(|hold| !hold.is_outright()) {
+        Some(hold) => {
+            let allowed = hold
+                .allows(&row.installed_version, &row.available_version)
+                .map_err(|e| {
+                    format!(
+                        "{}: invalid hold for {name}: {e}",
+                        HOLDS_FILE_PATH.display()
+                    )
+                })?;
+            if allowed {
+                Ok(Some(PackageUpdate::Available(row)))
+            } else {
+                Ok(Some(PackageUpdate::HeldBack(row, hold.clone())))
+            }
         }
+        None => Ok(Some(PackageUpdate::Available(row))),
+    }
+}
+
+/// The holds that apply today; a hold list that cannot be read holds nothing.
+fn active_holds() -> Holds {
+    holds::active_holds(&HOLDS_FILE_PATH, &holds::today()).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {e}", HOLDS_FILE_PATH.display());
+        Holds::new()
     })
 }
 
-fn available_updates() -> Vec<UpdateRow> {
-    let mut updates: Vec<(usize, UpdateRow)> = INSTALLED_PACKAGES
+/// Sorts `updates` into the sections of a report. Packages in `locker` are
+/// listed as locked unless `--include-locked` is given.
+fn sort_updates(
+    updates: Vec<PackageUpdate>,
+    locker: &HashSet<String>,
+    holds: &Holds,
+    context: &Context,
+) -> UpdateReport {
+    if context.options.include_locked {
+        UpdateReport::new(updates, &HashSet::new(), holds)
+    } else {
+        UpdateReport::new(updates, locker, holds)
+    }
+}
+
+fn available_updates(installed: &Installed, context: &Context) -> UpdateReport {
+    let holds = active_holds();
+    let mut warnings = context.warnings.clone();
+
+    let packages = &installed.packages;
+    let providers = installed_providers(packages);
+    let mut results: Vec<(usize, Result<PackageUpdate, String>)> = packages
         .par_iter()
         .enumerate()
-        .filter_map(|(idx, package)| update_for_package(package).map(|row| (idx, row)))
+        .filter_map(|(idx, package)| {
+            update_for_package(package, context, &holds, &providers, &installed.libraries)
+                .transpose()
+                .map(|result| (idx, result))
+        })
         .collect();
 
-    updates.sort_unstable_by_key(|(idx, _)| *idx);
-    updates.into_iter().map(|(_, row)| row).collect()
+    results.sort_unstable_by_key(|(idx, _)| *idx);
+
+    let mut updates = Vec::new();
+    for (_, result) in results {
+        match result {
+            Ok(update) => updates.push(update),
+            Err(warning) => warnings.push(warning),
+        }
+    }
+
+    UpdateReport {
+        warnings,
+        ..sort_updates(updates, &locked_packages(), &holds, context)
+    }
 }
 
-fn format_update_table(rows: &[UpdateRow]) -> Vec<String> {
-    let header = ("Port", "Version", "Available");
-    let (name_w, inst_w, avail_w) = rows.iter().fold(
-        (header.0.len(), header.1.len(), header.2.len()),
-        |(name_w, inst_w, avail_w), row| {
-            (
-                name_w.max(row.name.len()),
-                inst_w.max(row.installed_version.len()),
-                avail_w.max(row.available_version.len()),
-            )
+/// Compares the installed libraries of a package with the `.footprint` of
+/// its port and returns the shared libraries whose soname changes. Ports
+/// without a footprint are left alone.
+fn soname_bumps(libraries: &[String], port_dir: &Path) -> Vec<SonameBump> {
+    footprint::read_footprint(port_dir)
+        .map(|footprint| footprint::soname_bumps(libraries, &footprint))
+        .unwrap_or_default()
+}
+
+/// Computes the updates of the installed packages. A package database that
+/// cannot be read leaves nothing to check but a warning.
+fn installed_report(context: &Context) -> (Installed, UpdateReport) {
+    match installed_packages() {
+        Ok(installed) => {
+            let report = available_updates(&installed, context);
+            (installed, report)
+        }
+        Err(e) => {
+            let mut warnings = context.warnings.clone();
+            warnings.push(format!("Failed to read the package database: {e}"));
+            let report = UpdateReport {
+                warnings,
+                ..UpdateReport::default()
+            };
+            (Installed::default(), report)
+        }
+    }
+}
+
+/// The width of a table cell, not counting colour escape sequences.
+fn cell_width(cell: &str) -> usize {
+    let mut width = 0;
+    let mut chars = cell.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Formats rows as columns padded to their widest cell, followed by a
+/// separator line below the header.
+fn format_table(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let widths = rows.iter().fold(
+        header
+            .iter()
+            .map(|title| cell_width(title))
+            .collect::<Vec<_>>(),
+        |widths, row| {
+            widths
+                .iter()
+                .zip(row)
+                .map(|(width, cell)| (*width).max(cell_width(cell)))
+                .collect()
         },
     );
 
-    let mut output = Vec::with_capacity(rows.len() + 2);
-    output.push(format!(
-        "{:<name_w$} {:<inst_w$} {:<avail_w$}",
-        header.0,
-        header.1,
-        header.2,
-        name_w = name_w,
-        inst_w = inst_w,
-        avail_w = avail_w
-    ));
-    output.push(format!(
-        "{:-<name_w$} {:-<inst_w$} {:-<avail_w$}",
-        "",
-        "",
-        "",
-        name_w = name_w,
-        inst_w = inst_w,
-        avail_w = avail_w
-    ));
+    let format_line = |cells: Vec<&str>, fill: char| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let padding = fill.to_string().repeat(width - cell_width(cell));
+                format!("{cell}{padding}")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
 
-    output.extend(rows.iter().map(|row| {
-        format!(
-            "{:<name_w$} {:<inst_w$} {:<avail_w$}",
-            row.name,
-            row.installed_version,
-            row.available_version,
-            name_w = name_w,
-            inst_w = inst_w,
-            avail_w = avail_w
-        )
-    }));
+    let mut output = Vec::with_capacity(rows.len() + 2);
+    output.push(format_line(header.to_vec(), ' '));
+    output.push(format_line(vec![""; header.len()], '-'));
+    output.extend(
+        rows.iter()
+            .map(|row| format_line(row.iter().map(String::as_str).collect(), ' ')),
+    );
 
     output
 }
 
-fn notify_mode(updates: &[UpdateRow]) -> Result<(), Box<dyn Error>> {
-    if libnotify::init("scun").is_err() {
-        return Err("Failed to initialize libnotify".into());
+fn kind_cell(kind: UpdateKind, colour: bool) -> String {
+    if !colour {
+        return kind.to_string();
     }
 
-    let output = format_update_table(updates);
-    let notification_body = output.join("\n");
-    let notification =
-        libnotify::Notification::new("Port Updates", Some(notification_body.as_str()), None);
-
-    notification.set_timeout(5000);
-    notification.show()?;
-
-    libnotify::uninit();
-    Ok(())
+    let code = match kind {
+        UpdateKind::Major => "1;31",
+        UpdateKind::Minor => "33",
+        UpdateKind::Patch => "32",
+        UpdateKind::Release => "2",
+    };
+    format!("\x1b[{code}m{kind}\x1b[0m")
 }
 
-fn print_output(updates: &[UpdateRow], mode: PrintMode) -> Vec<String> {
-    match mode {
-        PrintMode::Count => vec![updates.len().to_string()],
-        PrintMode::Icon => vec![format!("󰚰 {}", updates.len())],
-        PrintMode::Long => format_update_table(updates),
-    }
+fn update_cells(row: &UpdateRow, colour: bool) -> Vec<String> {
+    vec![
+        row.name.clone(),
+        row.installed_version.clone(),
+        row.available_version.clone(),
+        kind_cell(row.kind(), colour),
+        row.collection_name(),
+    ]
 }
 
-fn print_mode(updates: &[UpdateRow], mode: PrintMode) {
-    for line in print_output(updates, mode) {
-        println!("{line}");
-    }
+fn join_cells<T: ToString>(items: &[T]) -> Option<String> {
+    let cells: Vec<String> = items.iter().map(ToString::to_string).collect();
+    (!cells.is_empty()).then(|| cells.join(", "))
 }
 
-fn run(command: Command) -> Result<(), Box<dyn Error>> {
-    configure_rayon_threads();
-    let updates = available_updates();
+/// The title of a table column and the cell it shows for an update.
+type Column = (&'static str, fn(&UpdateRow) -> Option<String>);
 
-    match command {
-        Command::Notify => notify_mode(&updates)?,
-        Command::Print(mode) => print_mode(&updates, mode),
-    }
+/// Columns appended to the update tables when any row has something to show
+/// in them.
+const OPTIONAL_COLUMNS: [Column; 3] = [
+    ("Dependents", |row| {
+        row.dependents.map(|count| count.to_string())
+    }),
+    ("Soname Bumps", |row| join_cells(&row.soname_bumps)),
+    ("New Dependencies", |row| join_cells(&row.missing_depends)),
+];
 
-    Ok(())
+/// Formats a table of updates from the given cells of each row, adding the
+/// optional columns that are needed.
+fn format_update_rows(header: &[&str], rows: Vec<(&UpdateRow, Vec<String>)>) -> Vec<String> {
+    let columns: Vec<_> = OPTIONAL_COLUMNS
+        .iter()
+        .filter(|(_, cell)| rows.iter().any(|(row, _)| cell(row).is_some()))
+        .collect();
+
+    let mut header = header.to_vec();
+    header.extend(columns.iter().map(|(title, _)| *title));
+    let rows: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|(row, mut cells)| {
+            cells.extend(
+                columns
+                    .iter()
+                    .map(|(_, cell)| cell(row).unwrap_or_default()),
+            );
+            cells
+        })
+        .collect();
+    format_table(&header, &rows)
 }
 
-fn cli_args() -> Vec<String> {
-    env::args().skip(1).collect()
+fn format_update_table(rows: &[UpdateRow], colour: bool) -> Vec<String> {
+    let rows = rows
+        .iter()
+        .map(|row| (row, update_cells(row, colour)))
+        .collect();
+    format_update_rows(
+        &["Port", "Version", "Available", "Kind", "Collection"],
+        rows,
+    )
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    match parse_args(&cli_args()) {
-        Ok(CliAction::Help) => println!("{USAGE}"),
-        Ok(CliAction::Run(command)) => run(command)?,
-        Err(e) => {
-            eprintln!("{e}");
-            eprintln!("{USAGE}");
-            process::exit(2);
-        }
-    }
+fn format_held_table(held: &[(UpdateRow, Hold)], colour: bool) -> Vec<String> {
+    let rows = held
+        .iter()
+        .map(|(row, hold)| {
+            let mut cells = update_cells(row, colour);
+            cells.push(hold.until.clone().unwrap_or_else(|| "-".to_string()));
+            cells.push(hold.reason.clone().unwrap_or_else(|| "-".to_string()));
+            (row, cells)
+        })
+        .collect();
+    format_update_rows(
+        &[
+            "Port",
+            "Version",
+            "Available",
+            "Kind",
+            "Collection",
+            "Until",
+            "Reason",
+        ],
+        rows,
+    )
+}
 
-    Ok(())
+fn format_held_back_table(held_back: &[(UpdateRow, Hold)], colour: bool) -> Vec<String> {
+    let rows = held_back
+        .iter()
+        .map(|(row, hold)| {
+            let mut cells = update_cells(row, colour);
+            cells.push(hold.constraint());
+            cells.push(hold.reason.clone().unwrap_or_else(|| "-".to_string()));
+            (row, cells)
+        })
+        .collect();
+    format_update_rows(
+        &[
+            "Port",
+            "Version",
+            "Available",
+            "Kind",
+            "Collection",
+            "Constraint",
+            "Reason",
+        ],
+        rows,
+    )
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Formats every section of the report as tables, with the kinds of change
+/// coloured if `colour` is set.
+fn format_report(report: &UpdateReport, colour: bool) -> Vec<String> {
+    let mut output = format_update_table(&report.updates, colour);
+
+    if !report.locked.is_empty() {
+        output.push(String::new());
+        output.push("Locked:".to_string());
+        output.extend(format_update_table(&report.locked, colour));
+    }
+
+    if !report.held.is_empty() {
+        output.push(String::new());
+        output.push("Held:".to_string());
+        output.extend(format_held_table(&report.held, colour));
+    }
+
+    if !report.held_back.is_empty() {
+        output.push(String::new());
+        output.push("Held back:".to_string());
+        output.extend(format_held_back_table(&report.held_back, colour));
+    }
+
+    if !report.downgrades.is_empty() {
+        output.push(String::new());
+        output.push("Newer than the ports tree:".to_string());
+        output.extend(format_update_table(&report.downgrades, colour));
+    }
+
+    output
+}
+
+/// Returns the updates whose available version is newer than the one the
+/// last notification announced for that package.
+fn unannounced_updates(
+    updates: &[UpdateRow],
+    notified: &HashMap<String, String>,
+    context: &Context,
+) -> Vec<UpdateRow> {
+    updates
+        .iter()
+        .filter(|row| {
+            notified.get(&row.name).is_none_or(|version| {
+                let comparison = context.comparison_for(&row.name);
+                comparison.compare(&row.available_version, version) == Some(Ordering::Greater)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+fn notify_mode(report: &UpdateReport, all: bool, context: &Context) -> Result<(), Box<dyn Error>> {
+    let output = if all {
+        format_report(report, false)
+    } else {
+        let notified = read_notified_versions(&NOTIFIED_FILE_PATH).unwrap_or_default();
+        let new_updates = unannounced_updates(&report.updates, &notified, context);
+        if new_updates.is_empty() {
+            return Ok(());
+        }
+        format_update_table(&new_updates, false)
+    };
+
+    show_notification(&output.join("\n"))?;
+
+    let announced: HashMap<String, String> = report
+        .updates
+        .iter()
+        .map(|row| (row.name.clone(), row.available_version.clone()))
+        .collect();
+    save_notified_versions(&NOTIFIED_FILE_PATH, &announced)?;
+
+    Ok(())
+}
+
+fn show_notification(notification_body: &str) -> Result<(), Box<dyn Error>> {
+    if libnotify::init("scun").is_err() {
+        return Err("Failed to initialize libnotify".into());
+    }
+
+    let notification = libnotify::Notification::new("Port Updates", Some(notification_body), None);
+
+    notification.set_timeout(5000);
+    notification.show()?;
+
+    libnotify::uninit();
+    Ok(())
+}
+
+fn print_output(report: &UpdateReport, mode: PrintMode, colour: bool) -> Vec<String> {
+    match mode {
+        PrintMode::Count => vec![report.updates.len().to_string()],
+        PrintMode::Icon => vec![format!("󰚰 {}", report.updates.len())],
+        PrintMode::Long => format_report(report, colour),
+        PrintMode::Downgrades => format_update_table(&report.downgrades, colour),
+        PrintMode::Json => vec![json::format_report(report)],
+        PrintMode::Bar(thresholds) => vec![json::format_bar_block(report, thresholds)],
+    }
+}
+
+fn print_mode(mut report: UpdateReport, mode: PrintMode, view: &ReportView) {
+    report.apply_view(view);
+
+    let colour = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    for line in print_output(&report, mode, colour) {
+        println!("{line}");
+    }
+}
+
+fn print_warnings(report: &UpdateReport) {
+    for warning in &report.warnings {
+        eprintln!("Warning: {warning}");
+    }
+}
+
+fn start_watcher(repositories: &[Repository]) -> Option<Watcher> {
+    let mut watcher = match Watcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Failed to initialize inotify: {e}");
+            return None;
+        }
+    };
+
+    if let Err(e) = watcher.watch_dir(pkg_db_dir()) {
+        eprintln!("Failed to watch {}: {e}", pkg_db_dir().display());
+    }
+    // `scun hold` and `scun unhold` rewrite the hold list in place
+    if let Some(config_dir) = HOLDS_FILE_PATH.parent().filter(|dir| dir.is_dir()) {
+        if let Err(e) = watcher.watch_dir(config_dir) {
+            eprintln!("Failed to watch {}: {e}", config_dir.display());
+        }
+    }
+    for repo in repositories {
+        match watcher.watch_tree(&repo.path) {
+            Ok(failed) => {
+                if let Some((path, e)) = failed.first() {
+                    eprintln!(
+                        "Failed to watch {} of the ports in {}, e.g. {}: {e}",
+                        failed.len(),
+                        repo.path.display(),
+                        path.display()
+                    );
+                }
+            }
+            Err(e) => eprintln!("Failed to watch {}: {e}", repo.path.display()),
+        }
+    }
+
+    Some(watcher)
+}
+
+/// Recomputes the updates whenever the package database, a collection or the
+/// hold list changed, and calls `on_change` with every report that differs
+/// from the previous one. Without inotify the updates are recomputed every
+/// `interval`; with it a quiet `interval` only leads to a recomputation when
+/// the day changed, since holds may have expired.
+fn resident_loop<F>(
+    context: &Context,
+    interval: Duration,
+    mut on_change: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&UpdateReport, bool) -> Result<(), Box<dyn Error>>,
+{
+    let mut watcher = start_watcher(&context.repositories);
+    let mut last_report: Option<UpdateReport> = None;
+
+    loop {
+        let computed_on = holds::today();
+        let (_, report) = installed_report(context);
+        if last_report.as_ref() != Some(&report) {
+            print_warnings(&report);
+            on_change(&report, last_report.is_none())?;
+            last_report = Some(report);
+        }
+
+        match watcher.as_mut() {
+            Some(watcher) => {
+                while !watcher.wait_for_changes(Some(interval), CHANGE_DEBOUNCE)?
+                    && holds::today() == computed_on
+                {}
+            }
+            None => thread::sleep(interval),
+        }
+    }
+}
+
+/// Speaks the i3bar protocol: a header, then an endless array of status
+/// lines. A new line is only written when the set of updates changed.
+fn watch_mode(context: &Context, interval: Duration) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", json::I3BAR_HEADER)?;
+    writeln!(stdout, "[")?;
+
+    resident_loop(context, interval, |report, first| {
+        let separator = if first { "" } else { "," };
+        writeln!(stdout, "{separator}{}", json::format_i3bar_status(report))?;
+        stdout.flush()?;
+        Ok(())
+    })
+}
+
+fn write_status_file(path: &Path, report: &UpdateReport) -> io::Result<()> {
+    // Write and rename so readers never see a partially written report
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, format!("{}\n", json::format_report(report)))?;
+    fs::rename(temp_path, path)
+}
+
+fn daemon_mode(context: &Context, outputs: &DaemonOutputs) -> Result<(), Box<dyn Error>> {
+    resident_loop(context, DEFAULT_WATCH_INTERVAL, |report, _| {
+        if let Some(path) = &outputs.status_file {
+            if let Err(e) = write_status_file(path, report) {
+                eprintln!("Failed to write {}: {e}", path.display());
+            }
+        }
+
+        if outputs.notify && !report.updates.is_empty() {
+            if let Err(e) = notify_mode(report, false, context) {
+                eprintln!("Failed to send notification: {e}");
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn hold_mode(package: String, hold: Hold) -> Result<(), Box<dyn Error>> {
+    let today = holds::today();
+    let mut holds = holds::read_holds(&HOLDS_FILE_PATH)?;
+    holds.retain(|_, hold| hold.is_active(&today));
+    holds.insert(package, hold);
+    holds::save_holds(&HOLDS_FILE_PATH, &holds)?;
+    Ok(())
+}
+
+fn unhold_mode(package: &str) -> Result<(), Box<dyn Error>> {
+    let mut holds = holds::read_holds(&HOLDS_FILE_PATH)?;
+    if holds.remove(package).is_none() {
+        return Err(format!("{package} is not held").into());
+    }
+    holds::save_holds(&HOLDS_FILE_PATH, &holds)?;
+    Ok(())
+}
+
+fn list_holds_mode() -> Result<(), Box<dyn Error>> {
+    let holds = holds::active_holds(&HOLDS_FILE_PATH, &holds::today())?;
+    let rows: Vec<Vec<String>> = holds
+        .into_iter()
+        .map(|(package, hold)| {
+            vec![
+                package,
+                hold.constraint(),
+                hold.until.unwrap_or_else(|| "-".to_string()),
+                hold.reason.unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+
+    for line in format_table(&["Port", "Constraint", "Until", "Reason"], &rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Why an installed package has no usable port.
+#[derive(Debug, PartialEq, Eq)]
+enum OrphanReason {
+    NotFound,
+    /// The port directory exists, but its Pkgfile cannot be read.
+    UnreadablePkgfile(String),
+}
+
+impl std::fmt::Display for OrphanReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrphanReason::NotFound => write!(f, "no port in any collection"),
+            OrphanReason::UnreadablePkgfile(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+fn orphan_reason(name: &str, context: &Context) -> Option<OrphanReason> {
+    let Some(port_dir) = find_port(name, context) else {
+        return Some(OrphanReason::NotFound);
+    };
+
+    // Pkgfiles that can be read but not evaluated are warnings of the
+    // update report; the port itself still exists
+    match extract_pkgfile_version(&port_dir) {
+        Err(e @ PkgfileError::Io(_)) => Some(OrphanReason::UnreadablePkgfile(format!(
+            "{}: {e}",
+            port_dir.join("Pkgfile").display()
+        ))),
+        _ => None,
+    }
+}
+
+fn format_orphans(orphans: &[(&PackageInfo, OrphanReason)]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = orphans
+        .iter()
+        .map(|((name, version), reason)| {
+            vec![
+                name.clone(),
+                version.clone().unwrap_or_else(|| "unknown".to_string()),
+                reason.to_string(),
+            ]
+        })
+        .collect();
+    format_table(&["Port", "Version", "Reason"], &rows)
+}
+
+fn orphans_mode(context: &Context) -> Result<(), Box<dyn Error>> {
+    let packages = installed_packages()?.packages;
+    let orphans: Vec<(&PackageInfo, OrphanReason)> = packages
+        .par_iter()
+        .filter_map(|package| orphan_reason(&package.0, context).map(|reason| (package, reason)))
+        .collect();
+
+    for line in format_orphans(&orphans) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Lists the collections that offer a newer version of an installed
+/// package than the port scun and prt-get use, as table rows.
+fn shadowed_ports((name, _): &PackageInfo, context: &Context) -> Vec<Vec<String>> {
+    let Some(used) = find_port(name, context) else {
+        return Vec::new();
+    };
+    let Ok(used_version) = extract_pkgfile_version(&used) else {
+        return Vec::new();
+    };
+    let port = used
+        .file_name()
+        .map(|port| port.to_string_lossy().into_owned());
+    let port_collection = |port_dir: &Path| collection_name(port_dir.parent().unwrap_or(port_dir));
+
+    port_dirs(port.as_deref().unwrap_or(name), &context.repositories)
+        .filter(|port_dir| *port_dir != used)
+        .filter_map(|port_dir| {
+            let version = extract_pkgfile_version(&port_dir).ok()?;
+            let newer = context
+                .comparison_for(name)
+                .compare(&version, &used_version);
+            (newer == Some(Ordering::Greater)).then(|| {
+                vec![
+                    name.clone(),
+                    port_collection(&used),
+                    used_version.clone(),
+                    port_collection(&port_dir),
+                    version,
+                ]
+            })
+        })
+        .collect()
+}
+
+fn shadowed_mode(context: &Context) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<Vec<String>> = installed_packages()?
+        .packages
+        .par_iter()
+        .flat_map_iter(|package| shadowed_ports(package, context))
+        .collect();
+
+    let header = ["Port", "Collection", "Version", "Newer In", "Available"];
+    for line in format_table(&header, &rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn format_ordering(ordering: Option<Ordering>) -> &'static str {
+    match ordering {
+        Some(Ordering::Greater) => "newer",
+        Some(Ordering::Less) => "older",
+        Some(Ordering::Equal) => "equal",
+        None => "unordered",
+    }
+}
+
+/// Compares the port version of an installed package with both algorithms
+/// and returns a table row if they disagree.
+fn disagreement((name, version): &PackageInfo, context: &Context) -> Option<Vec<String>> {
+    let port_dir = find_port(name, context)?;
+    let available_version = extract_pkgfile_version(&port_dir).ok()?;
+    let installed_version = version.as_deref().unwrap_or("unknown");
+
+    let libversion = Comparison::Libversion.compare(&available_version, installed_version);
+    let prt_get = Comparison::PrtGet.compare(&available_version, installed_version);
+    (libversion != prt_get).then(|| {
+        vec![
+            name.clone(),
+            installed_version.to_string(),
+            available_version,
+            format_ordering(libversion).to_string(),
+            format_ordering(prt_get).to_string(),
+        ]
+    })
+}
+
+fn disagreements_mode(context: &Context) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<Vec<String>> = installed_packages()?
+        .packages
+        .par_iter()
+        .filter_map(|package| disagreement(package, context))
+        .collect();
+
+    let header = ["Port", "Version", "Available", "libversion", "prt-get"];
+    for line in format_table(&header, &rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn describe_comparison(available: &str, installed: &str, ordering: Option<Ordering>) -> String {
+    match ordering {
+        Some(Ordering::Greater) => format!("{available} is newer than {installed}"),
+        Some(Ordering::Less) => format!("{available} is older than {installed}"),
+        Some(Ordering::Equal) => format!("{available} is equal to {installed}"),
+        None => format!("{available} and {installed} cannot be ordered"),
+    }
+}
+
+/// Describes every step that decides whether a package is reported, given
+/// the packages locked in `locker` and the active `holds`. The verdict comes
+/// from `update_for_package` and `sort_updates` themselves, so it cannot
+/// drift from what the other modes report.
+fn explain_package(
+    name: &str,
+    installed: &Installed,
+    locker: &HashSet<String>,
+    holds: &Holds,
+    context: &Context,
+) -> Vec<String> {
+    let line = |label: &str, value: String| format!("{label:<12}{value}");
+    let mut lines = Vec::new();
+
+    let packages = &installed.packages;
+    let Some(package) = packages.iter().find(|(installed, _)| installed == name) else {
+        lines.push(line(
+            "Installed:",
+            "no, only installed packages are checked".to_string(),
+        ));
+        return lines;
+    };
+    let installed_version = package.1.as_deref().unwrap_or("unknown");
+    lines.push(line("Installed:", installed_version.to_string()));
+
+    let mut ports = vec![name];
+    if let Some(port) = aliased_port(name) {
+        lines.push(line("Alias:", format!("{name} is provided by port {port}")));
+        ports.push(port);
+    }
+    for port in ports {
+        for (repo, lookup) in lookup_port(port, &context.repositories) {
+            let result = match lookup {
+                PortLookup::Filtered => "not provided, excluded by the prtdir filter".to_string(),
+                PortLookup::Missing => "no port".to_string(),
+                PortLookup::Found(port_dir) => format!("found {}", port_dir.display()),
+            };
+            lines.push(line(
+                "Collection:",
+                format!("{port} in {}: {result}", repo.display()),
+            ));
+        }
+    }
+
+    let Some(port_dir) = find_port(name, context) else {
+        lines.push(line("Port:", "none, the package is skipped".to_string()));
+        return lines;
+    };
+    let selection = if context.options.prefer_higher() {
+        "highest version, preferhigher is enabled"
+    } else {
+        "first collection providing it"
+    };
+    lines.push(line(
+        "Port:",
+        format!("{} ({selection})", port_dir.display()),
+    ));
+
+    let pkgfile_path = port_dir.join("Pkgfile");
+    match pkgfile::read_pkgfile(&pkgfile_path) {
+        Ok(pkgfile) => lines.push(line(
+            "Pkgfile:",
+            format!("version {}, release {}", pkgfile.version, pkgfile.release),
+        )),
+        Err(e) => lines.push(line("Pkgfile:", format!("{}: {e}", pkgfile_path.display()))),
+    }
+
+    let comparison = context.comparison_for(name);
+    let source = if context.policies.contains_key(name) {
+        " (compare.conf)"
+    } else {
+        ""
+    };
+    if let Ok(available_version) = extract_pkgfile_version(&port_dir) {
+        let ordering = comparison.compare(&available_version, installed_version);
+        lines.push(line(
+            "Comparison:",
+            format!(
+                "{comparison}{source}: {}",
+                describe_comparison(&available_version, installed_version, ordering)
+            ),
+        ));
+    }
+
+    let locked = match (locker.contains(name), context.options.include_locked) {
+        (true, false) => "yes, locked in prt-get.locker",
+        (true, true) => "yes, locked in prt-get.locker, but --include-locked counts it",
+        (false, _) => "no",
+    };
+    lines.push(line("Locked:", locked.to_string()));
+
+    let hold = match holds.get(name) {
+        Some(hold) if hold.is_outright() => "all updates are held".to_string(),
+        Some(hold) => format!("updates outside {} are held back", hold.constraint()),
+        None => "no".to_string(),
+    };
+    lines.push(line("Held:", hold));
+
+    let providers = installed_providers(packages);
+    let libraries = &installed.libraries;
+    let result = match update_for_package(package, context, holds, &providers, libraries) {
+        Err(warning) => format!("not checked, {warning}"),
+        Ok(None) => "up to date".to_string(),
+        Ok(Some(update)) => {
+            let report = sort_updates(vec![update], locker, holds, context);
+            if let Some(row) = report.updates.first() {
+                format!("update to {} is reported", row.available_version)
+            } else if !report.locked.is_empty() {
+                "update is listed as locked and not counted".to_string()
+            } else if !report.held.is_empty() {
+                "update is listed as held and not counted".to_string()
+            } else if !report.held_back.is_empty() {
+                "update is listed as held back and not counted".to_string()
+            } else {
+                "installed version is newer than the port".to_string()
+            }
+        }
+    };
+    lines.push(line("Result:", result));
+
+    lines
+}
+
+/// Reads the dependencies of every installed package from its port and keeps
+/// those that are installed. Depending on the port an installed package is
+/// an alias for counts as depending on that package.
+fn installed_dependencies(packages: &[PackageInfo], context: &Context) -> Dependencies {
+    let installed = installed_providers(packages);
+    packages
+        .par_iter()
+        .filter_map(|(name, _)| {
+            let port_dir = find_port(name, context)?;
+            let depends = extract_pkgfile_depends(&port_dir).ok()?;
+            let depends = depends
+                .iter()
+                .filter_map(|port| installed.get(port.as_str()))
+                .map(|package| package.to_string())
+                .collect();
+            Some((name.clone(), depends))
+        })
+        .collect()
+}
+
+/// Formats the updates in build order, each paired with the name of its
+/// port, followed by the `prt-get update` command that installs them.
+fn format_plan(updates: &[(&UpdateRow, String)], colour: bool) -> Vec<String> {
+    if updates.is_empty() {
+        return vec!["No updates available".to_string()];
+    }
+
+    let rows = updates
+        .iter()
+        .enumerate()
+        .map(|(idx, (row, _))| {
+            let mut cells = vec![(idx + 1).to_string()];
+            cells.extend(update_cells(row, colour));
+            (*row, cells)
+        })
+        .collect();
+    let mut lines = format_update_rows(
+        &["Step", "Port", "Version", "Available", "Kind", "Collection"],
+        rows,
+    );
+
+    let ports: Vec<&str> = updates.iter().map(|(_, port)| port.as_str()).collect();
+    lines.push(String::new());
+    lines.push(format!("prt-get update {}", ports.join(" ")));
+    lines
+}
+
+fn plan_mode(context: &Context) {
+    let (installed, report) = current_report(context);
+    let packages = &installed.packages;
+
+    let pending: Vec<String> = report.updates.iter().map(|row| row.name.clone()).collect();
+    let plan = plan::build_order(&pending, &installed_dependencies(packages, context));
+    for cycle in &plan.cycles {
+        eprintln!("Warning: dependency cycle: {}", cycle.join(" -> "));
+    }
+
+    let updates: Vec<(&UpdateRow, String)> = plan
+        .order
+        .iter()
+        .filter_map(|name| report.updates.iter().find(|row| &row.name == name))
+        .map(|row| {
+            let port = find_port(&row.name, context)
+                .and_then(|port_dir| Some(port_dir.file_name()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| row.name.clone());
+            (row, port)
+        })
+        .collect();
+
+    let colour = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    for line in format_plan(&updates, colour) {
+        println!("{line}");
+    }
+}
+
+/// Fills in how many installed packages depend on each update.
+fn count_dependents(report: &mut UpdateReport, packages: &[PackageInfo], context: &Context) {
+    let dependents = plan::dependents(&installed_dependencies(packages, context));
+    for row in report.rows_mut() {
+        row.dependents = Some(plan::reverse_dependencies(&row.name, &dependents).len());
+    }
+}
+
+fn format_impact(
+    name: &str,
+    packages: &[PackageInfo],
+    dependents: &[(String, String)],
+) -> Vec<String> {
+    let rows: Vec<Vec<String>> = dependents
+        .iter()
+        .map(|(dependent, via)| {
+            let version = packages
+                .iter()
+                .find(|(installed, _)| installed == dependent)
+                .and_then(|(_, version)| version.clone())
+                .unwrap_or_else(|| "unknown".to_string());
+            vec![dependent.clone(), version, via.clone()]
+        })
+        .collect();
+
+    let direct = dependents.iter().filter(|(_, via)| via == name).count();
+    let mut lines = format_table(&["Port", "Version", "Depends On"], &rows);
+    lines.push(String::new());
+    lines.push(format!(
+        "{} installed packages depend on {name}, {direct} of them directly",
+        dependents.len()
+    ));
+    lines
+}
+
+fn impact_mode(name: &str, context: &Context) -> Result<(), Box<dyn Error>> {
+    let packages = installed_packages()?.packages;
+    if !packages.iter().any(|(installed, _)| installed == name) {
+        return Err(format!("{name} is not installed").into());
+    }
+
+    let dependents = plan::dependents(&installed_dependencies(&packages, context));
+    let dependents = plan::reverse_dependencies(name, &dependents);
+    for line in format_impact(name, &packages, &dependents) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn format_file_changes(changes: &[FileChange]) -> Vec<String> {
+    let attributes = |entry: &footprint::FootprintEntry| match &entry.target {
+        Some(target) => format!("{} {} -> {target}", entry.mode, entry.owner),
+        None => format!("{} {}", entry.mode, entry.owner),
+    };
+    let rows: Vec<Vec<String>> = changes
+        .iter()
+        .map(|change| {
+            let (kind, installed, port) = match change {
+                FileChange::Added(entry) => ("added", "-".to_string(), attributes(entry)),
+                FileChange::Removed(_) => ("removed", "-".to_string(), "-".to_string()),
+                FileChange::Changed { entry, installed } => {
+                    ("changed", attributes(installed), attributes(entry))
+                }
+            };
+            vec![kind.to_string(), change.path().to_string(), installed, port]
+        })
+        .collect();
+
+    let count =
+        |kind: fn(&FileChange) -> bool| changes.iter().filter(|change| kind(change)).count();
+    let mut lines = format_table(&["Change", "Path", "Installed", "Port"], &rows);
+    lines.push(String::new());
+    lines.push(format!(
+        "{} added, {} removed, {} changed",
+        count(|change| matches!(change, FileChange::Added(_))),
+        count(|change| matches!(change, FileChange::Removed(_))),
+        count(|change| matches!(change, FileChange::Changed { .. })),
+    ));
+    lines
+}
+
+fn diff_mode(name: &str, context: &Context) -> Result<(), Box<dyn Error>> {
+    let mut files = installed_files(&HashSet::from([name]))
+        .map_err(|e| format!("Failed to read installed files: {e}"))?;
+    let installed = files
+        .remove(name)
+        .ok_or_else(|| format!("{name} is not installed"))?;
+    let port_dir =
+        find_port(name, context).ok_or_else(|| format!("{name} has no port in any collection"))?;
+    let footprint = footprint::read_footprint(&port_dir).map_err(|e| {
+        format!(
+            "Failed to read {}: {e}",
+            port_dir.join(".footprint").display()
+        )
+    })?;
+
+    let owners = Owners::read();
+    let changes = footprint::diff_footprint(&installed, &footprint, |path| owners.entry(path));
+    for line in format_file_changes(&changes) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn current_report(context: &Context) -> (Installed, UpdateReport) {
+    let (installed, report) = installed_report(context);
+    print_warnings(&report);
+    (installed, report)
+}
+
+fn run(command: Command, options: Options) -> Result<(), Box<dyn Error>> {
+    configure_rayon_threads();
+    let context = Context::load(options);
+
+    match command {
+        Command::Notify { all } => notify_mode(&current_report(&context).1, all, &context)?,
+        Command::Print(mode, view) => {
+            let (installed, mut report) = current_report(&context);
+            if view.dependents {
+                count_dependents(&mut report, &installed.packages, &context);
+            }
+            print_mode(report, mode, &view)
+        }
+        Command::Watch(interval) => watch_mode(&context, interval)?,
+        Command::Daemon(outputs) => daemon_mode(&context, &outputs)?,
+        Command::Hold { package, hold } => hold_mode(package, hold)?,
+        Command::Unhold(package) => unhold_mode(&package)?,
+        Command::ListHolds => list_holds_mode()?,
+        Command::Orphans => orphans_mode(&context)?,
+        Command::Shadowed => shadowed_mode(&context)?,
+        Command::Disagreements => disagreements_mode(&context)?,
+        Command::Why(package) => {
+            let installed = installed_packages()?;
+            let (locker, holds) = (locked_packages(), active_holds());
+            for line in explain_package(&package, &installed, &locker, &holds, &context) {
+                println!("{line}");
+            }
+        }
+        Command::Plan => plan_mode(&context),
+        Command::Impact(package) => impact_mode(&package, &context)?,
+        Command::Diff(package) => diff_mode(&package, &context)?,
+    }
+
+    Ok(())
+}
+
+fn cli_args() -> Vec<String> {
+    env::args().skip(1).collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match parse_args(&cli_args()) {
+        Ok(CliAction::Help) => println!("{USAGE}"),
+        Ok(CliAction::Run(command, options)) => run(command, options)?,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ports::tests::{repository, write_file, TestDir};
 
     fn args(args: &[&str]) -> Vec<String> {
         args.iter().map(|arg| arg.to_string()).collect()
     }
 
-    fn update_rows() -> Vec<UpdateRow> {
-        vec![
-            UpdateRow {
-                name: "foo".to_string(),
-                installed_version: "1.0-1".to_string(),
-                available_version: "1.1-1".to_string(),
+    /// Writes a port with the given `version-release` to `repo`.
+    fn write_port(repo: &Path, name: &str, version: &str) {
+        let (version, release) = version::split_release(version);
+        let port = repo.join(name);
+        fs::create_dir_all(&port).expect("failed to create port directory");
+        write_file(
+            &port.join("Pkgfile"),
+            &format!(
+                "name={name}\nversion={version}\nrelease={}\n",
+                release.unwrap_or("1")
+            ),
+        );
+    }
+
+    /// A context searching `repos` in order, without preferhigher.
+    fn test_context(repos: &[&Path]) -> Context {
+        Context {
+            options: Options {
+                prefer_higher: Some(false),
+                ..Options::default()
+            },
+            repositories: repos.iter().map(repository).collect(),
+            ..Context::default()
+        }
+    }
+
+    fn update_rows() -> Vec<UpdateRow> {
+        vec![
+            UpdateRow {
+                name: "foo".to_string(),
+                installed_version: "1.0-1".to_string(),
+                available_version: "1.1-1".to_string(),
+                collection: PathBuf::from("/usr/ports/opt"),
+                ..Default::default()
+            },
+            UpdateRow {
+                name: "longer-package".to_string(),
+                installed_version: "2.0-1".to_string(),
+                available_version: "2.0-2".to_string(),
+                collection: PathBuf::from("/usr/ports/contrib"),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn available(rows: Vec<UpdateRow>) -> Vec<PackageUpdate> {
+        rows.into_iter().map(PackageUpdate::Available).collect()
+    }
+
+    #[test]
+    fn parse_args_defaults_print_to_count_mode() {
+        assert_eq!(
+            parse_args(&args(&["print"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Count, ReportView::default()),
+                Options::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_print_submodes() {
+        assert_eq!(
+            parse_args(&args(&["p", "--icon"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Icon, ReportView::default()),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--json"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Json, ReportView::default()),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "-l"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Long, ReportView::default()),
+                Options::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_bar_thresholds() {
+        assert_eq!(
+            parse_args(&args(&["print", "--bar"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Bar(Thresholds::default()), ReportView::default()),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["p", "--critical", "20", "-b", "--warning", "5"])),
+            Ok(CliAction::Run(
+                Command::Print(
+                    PrintMode::Bar(Thresholds {
+                        warning: 5,
+                        critical: 20
+                    }),
+                    ReportView::default()
+                ),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--bar", "--warning"])),
+            Err(CliError::MissingValue("--warning"))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--bar", "--critical", "many"])),
+            Err(CliError::InvalidValue {
+                option: "--critical",
+                value: "many".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "print",
+                "--bar",
+                "--warning",
+                "20",
+                "--critical",
+                "5"
+            ])),
+            Err(CliError::InvalidValue {
+                option: "--warning",
+                value: "20 is above --critical 5".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--bar", "--warning", "60"])),
+            Err(CliError::InvalidValue {
+                option: "--warning",
+                value: "60 is above --critical 50".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--long", "--warning", "5"])),
+            Err(CliError::UnexpectedArgument {
+                mode: "print",
+                argument: "--warning/--critical".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_watch_interval() {
+        assert_eq!(
+            parse_args(&args(&["watch"])),
+            Ok(CliAction::Run(
+                Command::Watch(DEFAULT_WATCH_INTERVAL),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["w", "--interval", "5"])),
+            Ok(CliAction::Run(
+                Command::Watch(Duration::from_secs(5)),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["watch", "--interval", "0"])),
+            Err(CliError::InvalidValue {
+                option: "--interval",
+                value: "0".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["watch", "--long"])),
+            Err(CliError::UnexpectedArgument {
+                mode: "watch",
+                argument: "--long".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_daemon_outputs() {
+        assert_eq!(
+            parse_args(&args(&["daemon"])),
+            Ok(CliAction::Run(
+                Command::Daemon(DaemonOutputs {
+                    notify: true,
+                    status_file: None
+                }),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["d", "--status-file", "/run/scun.json"])),
+            Ok(CliAction::Run(
+                Command::Daemon(DaemonOutputs {
+                    notify: false,
+                    status_file: Some(PathBuf::from("/run/scun.json"))
+                }),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["daemon", "--status-file", "out.json", "--notify"])),
+            Ok(CliAction::Run(
+                Command::Daemon(DaemonOutputs {
+                    notify: true,
+                    status_file: Some(PathBuf::from("out.json"))
+                }),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["daemon", "--status-file"])),
+            Err(CliError::MissingValue("--status-file"))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_notify_all() {
+        assert_eq!(
+            parse_args(&args(&["notify"])),
+            Ok(CliAction::Run(
+                Command::Notify { all: false },
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["n", "--all"])),
+            Ok(CliAction::Run(
+                Command::Notify { all: true },
+                Options::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn unannounced_updates_skips_already_notified_versions() {
+        let rows = update_rows();
+        let notified = HashMap::from([
+            ("foo".to_string(), "1.1-1".to_string()),
+            ("longer-package".to_string(), "2.0-1".to_string()),
+        ]);
+
+        let context = Context::default();
+        let new_updates = unannounced_updates(&rows, &notified, &context);
+        assert_eq!(new_updates, vec![rows[1].clone()]);
+        assert_eq!(unannounced_updates(&rows, &HashMap::new(), &context), rows);
+    }
+
+    #[test]
+    fn unannounced_updates_uses_the_comparison_policy() {
+        let rows = vec![UpdateRow {
+            name: "foo".to_string(),
+            installed_version: "1.0-1".to_string(),
+            available_version: "1.0a-1".to_string(),
+            ..Default::default()
+        }];
+        let notified = HashMap::from([("foo".to_string(), "1.0-1".to_string())]);
+        let context = Context {
+            policies: Policies::from([("foo".to_string(), Comparison::Lexical)]),
+            ..Context::default()
+        };
+
+        assert_eq!(unannounced_updates(&rows, &notified, &context), rows);
+    }
+
+    #[test]
+    fn parse_args_accepts_hold_and_unhold() {
+        assert_eq!(
+            parse_args(&args(&["hold"])),
+            Ok(CliAction::Run(Command::ListHolds, Options::default()))
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "hold",
+                "linux",
+                "--until",
+                "2024-12-31",
+                "--reason",
+                "LTS only"
+            ])),
+            Ok(CliAction::Run(
+                Command::Hold {
+                    package: "linux".to_string(),
+                    hold: Hold {
+                        reason: Some("LTS only".to_string()),
+                        until: Some("2024-12-31".to_string()),
+                        ..Hold::default()
+                    }
+                },
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["hold", "linux", "--until", "soon"])),
+            Err(CliError::InvalidValue {
+                option: "--until",
+                value: "soon".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "hold",
+                "linux",
+                "--allow",
+                ">=6.6,<6.8",
+                "--ignore-release"
+            ])),
+            Ok(CliAction::Run(
+                Command::Hold {
+                    package: "linux".to_string(),
+                    hold: Hold {
+                        allow: Some(">=6.6,<6.8".to_string()),
+                        ignore_release: true,
+                        ..Hold::default()
+                    }
+                },
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["hold", "linux", "--allow", ">="])),
+            Err(CliError::InvalidValue {
+                option: "--allow",
+                value: ">=".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["hold", "--reason", "why"])),
+            Err(CliError::MissingPackage("hold"))
+        );
+        assert_eq!(
+            parse_args(&args(&["unhold", "linux"])),
+            Ok(CliAction::Run(
+                Command::Unhold("linux".to_string()),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["unhold"])),
+            Err(CliError::MissingPackage("unhold"))
+        );
+    }
+
+    #[test]
+    fn held_packages_are_listed_separately() {
+        let holds = Holds::from([(
+            "longer-package".to_string(),
+            Hold {
+                reason: Some("wait for fix".to_string()),
+                ..Hold::default()
+            },
+        )]);
+        let report = UpdateReport::new(available(update_rows()), &HashSet::new(), &holds);
+
+        assert_eq!(print_output(&report, PrintMode::Count, false), vec!["1"]);
+        assert_eq!(
+            print_output(&report, PrintMode::Long, false)[3..],
+            [
+                "",
+                "Held:",
+                "Port           Version Available Kind    Collection Until Reason      ",
+                "-------------- ------- --------- ------- ---------- ----- ------------",
+                "longer-package 2.0-1   2.0-2     release contrib    -     wait for fix",
+            ]
+        );
+    }
+
+    #[test]
+    fn held_packages_are_padded_by_characters() {
+        let holds = Holds::from([(
+            "longer-package".to_string(),
+            Hold {
+                reason: Some("warte auf Lösung".to_string()),
+                ..Hold::default()
+            },
+        )]);
+        let report = UpdateReport::new(available(update_rows()), &HashSet::new(), &holds);
+
+        assert_eq!(
+            print_output(&report, PrintMode::Long, false)[5..],
+            [
+                "Port           Version Available Kind    Collection Until Reason          ",
+                "-------------- ------- --------- ------- ---------- ----- ----------------",
+                "longer-package 2.0-1   2.0-2     release contrib    -     warte auf Lösung",
+            ]
+        );
+    }
+
+    #[test]
+    fn held_back_updates_are_listed_with_their_constraint() {
+        let pin = Hold {
+            allow: Some("1.0".to_string()),
+            reason: Some("LTS".to_string()),
+            ..Hold::default()
+        };
+        let rows = update_rows();
+        let holds = Holds::from([("foo".to_string(), pin.clone())]);
+        let report = UpdateReport::new(
+            vec![
+                PackageUpdate::HeldBack(rows[0].clone(), pin),
+                PackageUpdate::Available(rows[1].clone()),
+            ],
+            &HashSet::new(),
+            &holds,
+        );
+
+        assert_eq!(print_output(&report, PrintMode::Count, false), vec!["1"]);
+        assert_eq!(
+            print_output(&report, PrintMode::Long, false)[3..],
+            [
+                "",
+                "Held back:",
+                "Port Version Available Kind  Collection Constraint Reason",
+                "---- ------- --------- ----- ---------- ---------- ------",
+                "foo  1.0-1   1.1-1     minor opt        1.0        LTS   ",
+            ]
+        );
+    }
+
+    #[test]
+    fn downgrades_are_listed_separately() {
+        let rows = update_rows();
+        let report = UpdateReport::new(
+            vec![
+                PackageUpdate::Available(rows[0].clone()),
+                PackageUpdate::Downgrade(UpdateRow {
+                    name: "local".to_string(),
+                    installed_version: "2.1-1".to_string(),
+                    available_version: "2.0-1".to_string(),
+                    collection: PathBuf::from("/usr/ports/opt"),
+                    ..Default::default()
+                }),
+            ],
+            &HashSet::new(),
+            &Holds::new(),
+        );
+
+        assert_eq!(print_output(&report, PrintMode::Count, false), vec!["1"]);
+        assert_eq!(
+            print_output(&report, PrintMode::Long, false)[3..],
+            [
+                "",
+                "Newer than the ports tree:",
+                "Port  Version Available Kind  Collection",
+                "----- ------- --------- ----- ----------",
+                "local 2.1-1   2.0-1     minor opt       ",
+            ]
+        );
+        assert_eq!(
+            print_output(&report, PrintMode::Downgrades, false),
+            print_output(&report, PrintMode::Long, false)[5..]
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--downgrades"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Downgrades, ReportView::default()),
+                Options::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_orphans() {
+        assert_eq!(
+            parse_args(&args(&["orphans", "--no-prefer-higher"])),
+            Ok(CliAction::Run(
+                Command::Orphans,
+                Options {
+                    prefer_higher: Some(false),
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["orphans", "foo"])),
+            Err(CliError::UnexpectedArgument {
+                mode: "orphans",
+                argument: "foo".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_comparison_anywhere() {
+        assert_eq!(
+            parse_args(&args(&["print", "--compare", "prt-get", "-l"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Long, ReportView::default()),
+                Options {
+                    comparison: Comparison::PrtGet,
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["disagreements"])),
+            Ok(CliAction::Run(Command::Disagreements, Options::default()))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--compare", "rpm"])),
+            Err(CliError::InvalidValue {
+                option: "--compare",
+                value: "rpm".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--compare"])),
+            Err(CliError::MissingValue("--compare"))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_why() {
+        assert_eq!(
+            parse_args(&args(&["why", "openssl", "--include-locked"])),
+            Ok(CliAction::Run(
+                Command::Why("openssl".to_string()),
+                Options {
+                    include_locked: true,
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["why"])),
+            Err(CliError::MissingPackage("why"))
+        );
+    }
+
+    #[test]
+    fn describe_comparison_names_the_newer_version() {
+        assert_eq!(
+            describe_comparison("1.1-1", "1.0-1", Some(Ordering::Greater)),
+            "1.1-1 is newer than 1.0-1"
+        );
+        assert_eq!(
+            describe_comparison("1.a-1", "1.1-1", None),
+            "1.a-1 and 1.1-1 cannot be ordered"
+        );
+    }
+
+    #[test]
+    fn format_update_table_adds_missing_dependencies_column() {
+        let mut rows = update_rows();
+        rows[0].missing_depends = vec![
+            MissingDependency {
+                name: "libfoo".to_string(),
+                collection: Some(PathBuf::from("/usr/ports/contrib")),
             },
-            UpdateRow {
-                name: "longer-package".to_string(),
-                installed_version: "2.0-1".to_string(),
-                available_version: "2.0-2".to_string(),
+            MissingDependency {
+                name: "gone".to_string(),
+                collection: None,
             },
-        ]
+        ];
+
+        assert_eq!(
+            format_update_table(&rows, false),
+            [
+                "Port           Version Available Kind    Collection New Dependencies                ",
+                "-------------- ------- --------- ------- ---------- --------------------------------",
+                "foo            1.0-1   1.1-1     minor   opt        libfoo (contrib), gone (no port)",
+                "longer-package 2.0-1   2.0-2     release contrib                                    ",
+            ]
+        );
     }
 
     #[test]
-    fn parse_args_defaults_print_to_count_mode() {
+    fn format_update_table_adds_dependents_column() {
+        let mut rows = update_rows();
+        rows[0].dependents = Some(12);
+        rows[1].dependents = Some(0);
+
         assert_eq!(
-            parse_args(&args(&["print"])),
-            Ok(CliAction::Run(Command::Print(PrintMode::Count)))
+            format_update_table(&rows, false),
+            [
+                "Port           Version Available Kind    Collection Dependents",
+                "-------------- ------- --------- ------- ---------- ----------",
+                "foo            1.0-1   1.1-1     minor   opt        12        ",
+                "longer-package 2.0-1   2.0-2     release contrib    0         ",
+            ]
         );
     }
 
     #[test]
-    fn parse_args_accepts_print_submodes() {
+    fn format_held_back_table_warns_about_soname_bumps() {
+        let mut row = update_rows().remove(0);
+        row.soname_bumps = vec![SonameBump {
+            old: "libfoo.so.1".to_string(),
+            new: "libfoo.so.2".to_string(),
+        }];
+        let hold = Hold {
+            allow: Some("1.0".to_string()),
+            ..Hold::default()
+        };
+
         assert_eq!(
-            parse_args(&args(&["p", "--icon"])),
-            Ok(CliAction::Run(Command::Print(PrintMode::Icon)))
+            format_held_back_table(&[(row, hold)], false),
+            [
+                "Port Version Available Kind  Collection Constraint Reason Soname Bumps              ",
+                "---- ------- --------- ----- ---------- ---------- ------ --------------------------",
+                "foo  1.0-1   1.1-1     minor opt        1.0        -      libfoo.so.1 -> libfoo.so.2",
+            ]
         );
+    }
+
+    #[test]
+    fn format_impact_lists_dependents_and_how_they_are_reached() {
+        let packages = [
+            ("curl".to_string(), Some("8.5.0-1".to_string())),
+            ("git".to_string(), Some("2.43.0-1".to_string())),
+        ];
+        let dependents = [
+            ("curl".to_string(), "openssl".to_string()),
+            ("git".to_string(), "curl".to_string()),
+        ];
+
         assert_eq!(
-            parse_args(&args(&["print", "-l"])),
-            Ok(CliAction::Run(Command::Print(PrintMode::Long)))
+            format_impact("openssl", &packages, &dependents),
+            [
+                "Port Version  Depends On",
+                "---- -------- ----------",
+                "curl 8.5.0-1  openssl   ",
+                "git  2.43.0-1 curl      ",
+                "",
+                "2 installed packages depend on openssl, 1 of them directly",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_file_changes_shows_installed_and_port_attributes() {
+        let entry = |mode: &str, path: &str| footprint::FootprintEntry {
+            mode: mode.to_string(),
+            owner: "root/root".to_string(),
+            path: path.to_string(),
+            target: None,
+        };
+        let link = |target: &str| footprint::FootprintEntry {
+            target: Some(target.to_string()),
+            ..entry("lrwxrwxrwx", "usr/lib/libfoo.so")
+        };
+        let changes = [
+            FileChange::Changed {
+                entry: entry("-rwsr-xr-x", "usr/bin/foo"),
+                installed: entry("-rwxr-xr-x", "usr/bin/foo"),
+            },
+            FileChange::Changed {
+                entry: link("libfoo.so.2"),
+                installed: link("libfoo.so.1"),
+            },
+            FileChange::Added(entry("-rw-r--r--", "usr/lib/libfoo.so.2")),
+            FileChange::Removed("usr/lib/libfoo.so.1".to_string()),
+        ];
+
+        assert_eq!(
+            format_file_changes(&changes),
+            [
+                "Change  Path                Installed                           Port                               ",
+                "------- ------------------- ----------------------------------- -----------------------------------",
+                "changed usr/bin/foo         -rwxr-xr-x root/root                -rwsr-xr-x root/root               ",
+                "changed usr/lib/libfoo.so   lrwxrwxrwx root/root -> libfoo.so.1 lrwxrwxrwx root/root -> libfoo.so.2",
+                "added   usr/lib/libfoo.so.2 -                                   -rw-r--r-- root/root               ",
+                "removed usr/lib/libfoo.so.1 -                                   -                                  ",
+                "",
+                "1 added, 1 removed, 2 changed",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_plan_lists_steps_and_prt_get_command() {
+        let row = |name: &str| UpdateRow {
+            name: name.to_string(),
+            installed_version: "1.0-1".to_string(),
+            available_version: "1.0-2".to_string(),
+            collection: PathBuf::from("/usr/ports/core"),
+            ..Default::default()
+        };
+        let (zlib, openssh) = (row("zlib"), row("openssh"));
+
+        assert_eq!(
+            format_plan(
+                &[
+                    (&zlib, "zlib".to_string()),
+                    (&openssh, "openssh".to_string())
+                ],
+                false
+            ),
+            [
+                "Step Port    Version Available Kind    Collection",
+                "---- ------- ------- --------- ------- ----------",
+                "1    zlib    1.0-1   1.0-2     release core      ",
+                "2    openssh 1.0-1   1.0-2     release core      ",
+                "",
+                "prt-get update zlib openssh",
+            ]
+        );
+        assert_eq!(format_plan(&[], false), ["No updates available"]);
+    }
+
+    #[test]
+    fn parse_args_accepts_diff() {
+        assert_eq!(
+            parse_args(&args(&["diff", "openssl"])),
+            Ok(CliAction::Run(
+                Command::Diff("openssl".to_string()),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["diff"])),
+            Err(CliError::MissingPackage("diff"))
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_plan() {
+        assert_eq!(
+            parse_args(&args(&["plan", "--include-locked"])),
+            Ok(CliAction::Run(
+                Command::Plan,
+                Options {
+                    include_locked: true,
+                    ..Options::default()
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn shadowed_ports_finds_newer_ports_in_later_collections() {
+        let temp = TestDir::new("shadowed");
+        let (overlay, opt, contrib) = (
+            temp.path().join("overlay"),
+            temp.path().join("opt"),
+            temp.path().join("contrib"),
+        );
+        write_port(&overlay, "foo", "1.0-1");
+        write_port(&opt, "foo", "1.1-1");
+        write_port(&contrib, "foo", "0.9-1");
+        write_port(&opt, "bar", "2.0-1");
+        let context = test_context(&[&overlay, &opt, &contrib]);
+        let package = |name: &str| (name.to_string(), Some("1.0-1".to_string()));
+
+        assert_eq!(
+            shadowed_ports(&package("foo"), &context),
+            [["foo", "overlay", "1.0-1", "opt", "1.1-1"]]
+        );
+        assert!(shadowed_ports(&package("bar"), &context).is_empty());
+        assert!(shadowed_ports(&package("missing"), &context).is_empty());
+    }
+
+    #[test]
+    fn parse_args_accepts_shadowed() {
+        assert_eq!(
+            parse_args(&args(&["shadowed"])),
+            Ok(CliAction::Run(Command::Shadowed, Options::default()))
+        );
+        assert_eq!(
+            parse_args(&args(&["shadowed", "-l"])),
+            Err(CliError::UnexpectedArgument {
+                mode: "shadowed",
+                argument: "-l".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn count_dependents_fills_every_section() {
+        let temp = TestDir::new("count-dependents");
+        let opt = temp.path().join("opt");
+        for (name, depends) in [
+            ("zlib", ""),
+            ("openssl", "zlib"),
+            ("curl", "openssl zlib"),
+            ("git", "curl"),
+            ("local", ""),
+        ] {
+            fs::create_dir_all(opt.join(name)).expect("failed to create port directory");
+            write_file(
+                &opt.join(name).join("Pkgfile"),
+                &format!("# Depends on: {depends}\nname={name}\nversion=1.0\nrelease=1\n"),
+            );
+        }
+        let context = test_context(&[&opt]);
+        let packages: Vec<PackageInfo> = ["zlib", "openssl", "curl", "git", "local"]
+            .iter()
+            .map(|name| (name.to_string(), Some("1.0-1".to_string())))
+            .collect();
+        let row = |name: &str| UpdateRow {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut report = UpdateReport {
+            updates: vec![row("zlib")],
+            locked: vec![row("openssl")],
+            held: vec![(row("curl"), Hold::default())],
+            held_back: vec![(row("git"), Hold::default())],
+            downgrades: vec![row("local")],
+            ..Default::default()
+        };
+
+        count_dependents(&mut report, &packages, &context);
+        let counts: Vec<_> = report
+            .rows_mut()
+            .map(|row| (row.name.clone(), row.dependents))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("zlib".to_string(), Some(3)),
+                ("openssl".to_string(), Some(2)),
+                ("curl".to_string(), Some(1)),
+                ("git".to_string(), Some(0)),
+                ("local".to_string(), Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_package_follows_locks_the_way_reports_do() {
+        let temp = TestDir::new("explain");
+        let (core, opt) = (temp.path().join("core"), temp.path().join("opt"));
+        write_port(&opt, "foo", "1.1-1");
+        let mut context = test_context(&[&core, &opt]);
+        let installed = Installed {
+            packages: vec![("foo".to_string(), Some("1.0-1".to_string()))],
+            ..Installed::default()
+        };
+        let locker = HashSet::from(["foo".to_string()]);
+
+        let lines = explain_package("foo", &installed, &locker, &Holds::new(), &context);
+        assert_eq!(
+            lines,
+            [
+                "Installed:  1.0-1".to_string(),
+                format!("Collection: foo in {}: no port", core.display()),
+                format!(
+                    "Collection: foo in {}: found {}",
+                    opt.display(),
+                    opt.join("foo").display()
+                ),
+                format!(
+                    "Port:       {} (first collection providing it)",
+                    opt.join("foo").display()
+                ),
+                "Pkgfile:    version 1.1, release 1".to_string(),
+                "Comparison: libversion: 1.1-1 is newer than 1.0-1".to_string(),
+                "Locked:     yes, locked in prt-get.locker".to_string(),
+                "Held:       no".to_string(),
+                "Result:     update is listed as locked and not counted".to_string(),
+            ]
+        );
+
+        context.options.include_locked = true;
+        let lines = explain_package("foo", &installed, &locker, &Holds::new(), &context);
+        assert_eq!(
+            lines[6..],
+            [
+                "Locked:     yes, locked in prt-get.locker, but --include-locked counts it",
+                "Held:       no",
+                "Result:     update to 1.1-1 is reported",
+            ]
+        );
+    }
+
+    #[test]
+    fn update_for_package_lists_dependencies_that_are_not_installed() {
+        let temp = TestDir::new("missing-depends");
+        let (opt, contrib) = (temp.path().join("opt"), temp.path().join("contrib"));
+        write_port(&opt, "zlib", "1.3-1");
+        write_port(&contrib, "libfoo", "1.0-1");
+        fs::create_dir_all(opt.join("app")).expect("failed to create port directory");
+        write_file(
+            &opt.join("app").join("Pkgfile"),
+            "# Depends on: zlib libfoo libgone\nname=app\nversion=2.0\nrelease=1\n",
+        );
+        let context = test_context(&[&opt, &contrib]);
+        let packages = [
+            ("app".to_string(), Some("1.0-1".to_string())),
+            ("zlib".to_string(), Some("1.3-1".to_string())),
+        ];
+        let installed = installed_providers(&packages);
+
+        let Ok(Some(PackageUpdate::Available(row))) = update_for_package(
+            &packages[0],
+            &context,
+            &Holds::new(),
+            &installed,
+            &InstalledFiles::new(),
+        ) else {
+            panic!("expected an update of app");
+        };
+        assert_eq!(row.available_version, "2.0-1");
+        assert_eq!(
+            row.missing_depends,
+            [
+                MissingDependency {
+                    name: "libfoo".to_string(),
+                    collection: Some(contrib.clone()),
+                },
+                MissingDependency {
+                    name: "libgone".to_string(),
+                    collection: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn update_for_package_compares_installed_libraries_with_the_footprint() {
+        let temp = TestDir::new("soname-bumps");
+        let core = temp.path().join("core");
+        write_port(&core, "icu", "75.1-1");
+        write_port(&core, "zstd", "1.6-1");
+        write_file(
+            &core.join("icu").join(".footprint"),
+            "-rwxr-xr-x\troot/root\tusr/lib/libicuuc.so.75.1\n",
+        );
+        let context = test_context(&[&core]);
+        let packages = [
+            ("icu".to_string(), Some("74.2-1".to_string())),
+            ("zstd".to_string(), Some("1.5-1".to_string())),
+        ];
+        let libraries = InstalledFiles::from([
+            (
+                "icu".to_string(),
+                vec!["usr/lib/libicuuc.so.74.2".to_string()],
+            ),
+            (
+                "zstd".to_string(),
+                vec!["usr/lib/libzstd.so.1.5".to_string()],
+            ),
+        ]);
+        let update = |package| match update_for_package(
+            package,
+            &context,
+            &Holds::new(),
+            &installed_providers(&packages),
+            &libraries,
+        ) {
+            Ok(Some(PackageUpdate::Available(row))) => row,
+            other => panic!("expected an update, got {other:?}"),
+        };
+
+        assert_eq!(
+            update(&packages[0]).soname_bumps,
+            [SonameBump {
+                old: "libicuuc.so.74".to_string(),
+                new: "libicuuc.so.75".to_string(),
+            }]
+        );
+        // zstd has no footprint to compare with
+        assert!(update(&packages[1]).soname_bumps.is_empty());
+    }
+
+    #[test]
+    fn orphan_reason_tells_missing_ports_from_unreadable_pkgfiles() {
+        let temp = TestDir::new("orphans");
+        let opt = temp.path().join("opt");
+        write_port(&opt, "foo", "1.0-1");
+        fs::create_dir_all(opt.join("broken")).expect("failed to create port directory");
+        fs::create_dir_all(opt.join("odd")).expect("failed to create port directory");
+        write_file(&opt.join("odd").join("Pkgfile"), "version=$(date)\n");
+        let context = test_context(&[&opt]);
+
+        assert_eq!(orphan_reason("foo", &context), None);
+        assert_eq!(
+            orphan_reason("gone", &context),
+            Some(OrphanReason::NotFound)
+        );
+        assert!(matches!(
+            orphan_reason("broken", &context),
+            Some(OrphanReason::UnreadablePkgfile(e))
+                if e.starts_with(&format!("{}: ", opt.join("broken/Pkgfile").display()))
+        ));
+        // Pkgfiles that cannot be evaluated are warnings of the report
+        assert_eq!(orphan_reason("odd", &context), None);
+    }
+
+    #[test]
+    fn format_orphans_distinguishes_missing_ports_from_unreadable_pkgfiles() {
+        let gone = ("oldlib".to_string(), Some("1.0-1".to_string()));
+        let broken = ("foo".to_string(), Some("2.0-1".to_string()));
+        let orphans = [
+            (&gone, OrphanReason::NotFound),
+            (
+                &broken,
+                OrphanReason::UnreadablePkgfile("/usr/ports/opt/foo/Pkgfile: denied".to_string()),
+            ),
+        ];
+
+        assert_eq!(
+            format_orphans(&orphans),
+            [
+                "Port   Version Reason                            ",
+                "------ ------- ----------------------------------",
+                "oldlib 1.0-1   no port in any collection         ",
+                "foo    2.0-1   /usr/ports/opt/foo/Pkgfile: denied",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_prefer_higher_anywhere() {
+        assert_eq!(
+            parse_args(&args(&["--prefer-higher", "print", "-l"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Long, ReportView::default()),
+                Options {
+                    prefer_higher: Some(true),
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["notify", "--no-prefer-higher"])),
+            Ok(CliAction::Run(
+                Command::Notify { all: false },
+                Options {
+                    prefer_higher: Some(false),
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["p", "--include-locked"])),
+            Ok(CliAction::Run(
+                Command::Print(PrintMode::Count, ReportView::default()),
+                Options {
+                    include_locked: true,
+                    ..Options::default()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["hold", "foo", "--reason", "--include-locked"])),
+            Ok(CliAction::Run(
+                Command::Hold {
+                    package: "foo".to_string(),
+                    hold: Hold {
+                        reason: Some("--include-locked".to_string()),
+                        ..Hold::default()
+                    }
+                },
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--sort", "--compare", "prt-get"])),
+            Err(CliError::InvalidValue {
+                option: "--sort",
+                value: "--compare".to_string()
+            })
         );
     }
 
@@ -368,20 +3074,214 @@ mod tests {
 
     #[test]
     fn print_count_ignores_long_table_header_lines() {
-        let rows = update_rows();
+        let report = UpdateReport::new(available(update_rows()), &HashSet::new(), &Holds::new());
+
+        assert_eq!(print_output(&report, PrintMode::Count, false), vec!["2"]);
+        assert_eq!(print_output(&report, PrintMode::Icon, false), vec!["󰚰 2"]);
+        assert_eq!(print_output(&report, PrintMode::Long, false).len(), 4);
+    }
+
+    #[test]
+    fn locked_packages_are_listed_separately() {
+        let report = UpdateReport::new(
+            available(update_rows()),
+            &HashSet::from(["foo".to_string()]),
+            &Holds::new(),
+        );
+
+        assert_eq!(print_output(&report, PrintMode::Count, false), vec!["1"]);
+        assert_eq!(
+            print_output(&report, PrintMode::Long, false),
+            vec![
+                "Port           Version Available Kind    Collection",
+                "-------------- ------- --------- ------- ----------",
+                "longer-package 2.0-1   2.0-2     release contrib   ",
+                "",
+                "Locked:",
+                "Port Version Available Kind  Collection",
+                "---- ------- --------- ----- ----------",
+                "foo  1.0-1   1.1-1     minor opt       ",
+            ]
+        );
+    }
+
+    #[test]
+    fn print_json_emits_documented_fields() {
+        let mut report = UpdateReport::new(
+            available(update_rows()),
+            &HashSet::from(["foo".to_string()]),
+            &Holds::new(),
+        );
+        report
+            .warnings
+            .push("bar/Pkgfile: version is not set".to_string());
+
+        let output = print_output(&report, PrintMode::Json, false);
+        let document: serde_json::Value =
+            serde_json::from_str(&output[0]).expect("print --json emitted invalid JSON");
+
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "format_version": 1,
+                "total": 1,
+                "locked_total": 1,
+                "held_total": 0,
+                "held_back_total": 0,
+                "downgrades_total": 0,
+                "updates": [{
+                    "name": "longer-package",
+                    "installed": "2.0-1",
+                    "available": "2.0-2",
+                    "kind": "release",
+                    "collection": "/usr/ports/contrib",
+                    "collection_name": "contrib",
+                    "missing_dependencies": [],
+                    "soname_bumps": []
+                }],
+                "locked": [{
+                    "name": "foo",
+                    "installed": "1.0-1",
+                    "available": "1.1-1",
+                    "kind": "minor",
+                    "collection": "/usr/ports/opt",
+                    "collection_name": "opt",
+                    "missing_dependencies": [],
+                    "soname_bumps": []
+                }],
+                "held": [],
+                "held_back": [],
+                "downgrades": [],
+                "warnings": ["bar/Pkgfile: version is not set"]
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_kind_filter_and_sort() {
+        assert_eq!(
+            parse_args(&args(&["print", "--kind", "major,minor", "--sort", "kind"])),
+            Ok(CliAction::Run(
+                Command::Print(
+                    PrintMode::Count,
+                    ReportView {
+                        kinds: vec![UpdateKind::Major, UpdateKind::Minor],
+                        sort: SortOrder::Kind,
+                        ..ReportView::default()
+                    }
+                ),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--kind", "major,huge"])),
+            Err(CliError::InvalidValue {
+                option: "--kind",
+                value: "major,huge".to_string()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "-l", "--sort", "size"])),
+            Err(CliError::InvalidValue {
+                option: "--sort",
+                value: "size".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_accepts_dependents_with_long() {
+        assert_eq!(
+            parse_args(&args(&["print", "--dependents", "-l"])),
+            Ok(CliAction::Run(
+                Command::Print(
+                    PrintMode::Long,
+                    ReportView {
+                        dependents: true,
+                        ..ReportView::default()
+                    }
+                ),
+                Options::default()
+            ))
+        );
+        assert_eq!(
+            parse_args(&args(&["print", "--json", "--dependents"])),
+            Err(unexpected_print_argument("--dependents"))
+        );
+    }
+
+    #[test]
+    fn apply_view_filters_and_sorts_updates() {
+        let mut rows = update_rows();
+        rows.push(UpdateRow {
+            name: "bar".to_string(),
+            installed_version: "1.0-1".to_string(),
+            available_version: "2.0-1".to_string(),
+            collection: PathBuf::from("/usr/ports/opt"),
+            ..Default::default()
+        });
+        let report = || UpdateReport::new(available(rows.clone()), &HashSet::new(), &Holds::new());
+
+        let mut sorted = report();
+        sorted.apply_view(&ReportView {
+            sort: SortOrder::Kind,
+            ..ReportView::default()
+        });
+        let names: Vec<&str> = sorted.updates.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, ["bar", "foo", "longer-package"]);
+
+        let mut rebuilds = report();
+        rebuilds.apply_view(&ReportView {
+            kinds: vec![UpdateKind::Release],
+            ..ReportView::default()
+        });
+        assert_eq!(print_output(&rebuilds, PrintMode::Count, false), vec!["1"]);
+
+        let mut opt = report();
+        opt.apply_view(&ReportView {
+            repos: vec!["opt".to_string()],
+            ..ReportView::default()
+        });
+        assert_eq!(print_output(&opt, PrintMode::Count, false), vec!["2"]);
+
+        let mut contrib = report();
+        contrib.apply_view(&ReportView {
+            repos: vec!["/usr/ports/contrib".to_string()],
+            ..ReportView::default()
+        });
+        assert_eq!(contrib.updates, update_rows()[1..]);
+    }
 
-        assert_eq!(print_output(&rows, PrintMode::Count), vec!["2"]);
-        assert_eq!(print_output(&rows, PrintMode::Icon), vec!["󰚰 2"]);
-        assert_eq!(print_output(&rows, PrintMode::Long).len(), rows.len() + 2);
+    #[test]
+    fn colour_codes_do_not_count_towards_column_widths() {
+        let output = format_update_table(&update_rows()[..1], true);
+
+        assert_eq!(output[0], "Port Version Available Kind  Collection");
+        assert_eq!(
+            output[2],
+            "foo  1.0-1   1.1-1     \x1b[33mminor\x1b[0m opt       "
+        );
     }
 
     #[test]
     fn format_update_table_uses_dynamic_widths() {
-        let output = format_update_table(&update_rows());
+        let output = format_update_table(&update_rows(), false);
 
-        assert_eq!(output[0], "Port           Version Available");
-        assert_eq!(output[1], "-------------- ------- ---------");
-        assert_eq!(output[2], "foo            1.0-1   1.1-1    ");
-        assert_eq!(output[3], "longer-package 2.0-1   2.0-2    ");
+        assert_eq!(
+            output[0],
+            "Port           Version Available Kind    Collection"
+        );
+        assert_eq!(
+            output[1],
+            "-------------- ------- --------- ------- ----------"
+        );
+        assert_eq!(
+            output[2],
+            "foo            1.0-1   1.1-1     minor   opt       "
+        );
+        assert_eq!(
+            output[3],
+            "longer-package 2.0-1   2.0-2     release contrib   "
+        );
     }
 }
@@ -0,0 +1,826 @@
+//! A small, non-executing evaluator for the variable assignments at the top of
+//! a Pkgfile. It understands quoting, `$var`/`${var}` expansion and the simple
+//! parameter substitutions ports use for version strings, and stops at the
+//! first function definition. Assignments it cannot evaluate, e.g. command
+//! substitutions, are skipped; only `name`, `version` and `release` have to
+//! be evaluated. The `# Depends on:` line of the header comment is read as
+//! well.
+
+use core::fmt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pkgfile {
+    pub name: Option<String>,
+    pub version: String,
+    pub release: String,
+    pub depends: Vec<String>,
+}
+
+impl Pkgfile {
+    /// The `version-release` string pkgadd records for the package.
+    pub fn version_release(&self) -> String {
+        format!("{}-{}", self.version, self.release)
+    }
+}
+
+#[derive(Debug)]
+pub enum PkgfileError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+    Missing(&'static str),
+    NameMismatch { port: String, name: String },
+}
+
+impl fmt::Display for PkgfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PkgfileError::Io(e) => write!(f, "IO error: {e}"),
+            PkgfileError::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            PkgfileError::Missing(variable) => write!(f, "{variable} is not set"),
+            PkgfileError::NameMismatch { port, name } => {
+                write!(f, "name={name} does not match port directory {port}")
+            }
+        }
+    }
+}
+
+impl Error for PkgfileError {}
+
+impl From<io::Error> for PkgfileError {
+    fn from(error: io::Error) -> Self {
+        PkgfileError::Io(error)
+    }
+}
+
+pub fn read_pkgfile(path: &Path) -> Result<Pkgfile, PkgfileError> {
+    parse_pkgfile(&fs::read_to_string(path)?)
+}
+
+pub fn parse_pkgfile(contents: &str) -> Result<Pkgfile, PkgfileError> {
+    let Parser {
+        mut variables,
+        mut skipped,
+        ..
+    } = evaluate(contents)?;
+    if let Some((line, message)) = ["name", "version", "release"]
+        .iter()
+        .find_map(|variable| skipped.remove(*variable))
+    {
+        return Err(PkgfileError::Syntax { line, message });
+    }
+    let mut take = |variable: &'static str| {
+        variables
+            .remove(variable)
+            .filter(|value| !value.is_empty())
+            .ok_or(PkgfileError::Missing(variable))
+    };
+
+    Ok(Pkgfile {
+        name: take("name").ok(),
+        version: take("version")?,
+        release: take("release")?,
+        depends: parse_depends(contents),
+    })
+}
+
+/// Reads the ports listed in the `# Depends on:` header line, which may be
+/// separated by whitespace or commas.
+fn parse_depends(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map_while(|line| line.strip_prefix('#'))
+        .find_map(|comment| comment.trim_start().strip_prefix("Depends on:"))
+        .map(|depends| {
+            depends
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|port| !port.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn evaluate(contents: &str) -> Result<Parser, PkgfileError> {
+    let mut parser = Parser {
+        chars: contents.chars().collect(),
+        pos: 0,
+        line: 1,
+        variables: HashMap::new(),
+        skipped: HashMap::new(),
+        unsupported: None,
+    };
+    parser.run()?;
+    Ok(parser)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    variables: HashMap<String, String>,
+    /// Assignments that could not be evaluated, with the line and reason.
+    skipped: HashMap<String, (usize, String)>,
+    /// Why the word being read cannot be evaluated.
+    unsupported: Option<(usize, String)>,
+}
+
+impl Parser {
+    fn run(&mut self) -> Result<(), PkgfileError> {
+        loop {
+            self.skip_blanks_and_comments();
+            let Some(c) = self.peek() else {
+                return Ok(());
+            };
+
+            if c == ';' {
+                self.bump();
+                continue;
+            }
+
+            let start = self.pos;
+            let identifier = self.read_identifier();
+            if !identifier.is_empty() && self.peek() == Some('=') {
+                self.bump();
+                self.assignment(identifier)?;
+                continue;
+            }
+
+            if identifier == "function" || (!identifier.is_empty() && self.at_function_definition())
+            {
+                // Everything scun needs is declared before build()
+                return Ok(());
+            }
+
+            // Any other top-level command is never executed
+            self.pos = start;
+            self.skip_line();
+        }
+    }
+
+    fn assignment(&mut self, identifier: String) -> Result<(), PkgfileError> {
+        if self.peek() == Some('(') {
+            return self.skip_parentheses();
+        }
+
+        let value = self.word(&[' ', '\t', '\n', ';'])?;
+        match self.unsupported.take() {
+            Some(reason) => {
+                self.variables.remove(&identifier);
+                self.skipped.insert(identifier, reason);
+            }
+            None => {
+                self.skipped.remove(&identifier);
+                self.variables.insert(identifier, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn at_function_definition(&mut self) -> bool {
+        let start = self.pos;
+        self.skip_spaces();
+        let is_function = self.peek() == Some('(') && {
+            self.bump();
+            self.skip_spaces();
+            self.peek() == Some(')')
+        };
+        self.pos = start;
+        is_function
+    }
+
+    fn skip_parentheses(&mut self) -> Result<(), PkgfileError> {
+        let line = self.line;
+        let mut depth = 0;
+        while let Some(c) = self.bump() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '\'' => self.quoted_until('\'', line)?,
+                '"' => self.quoted_until('"', line)?,
+                '\\' => {
+                    self.bump();
+                }
+                _ => {}
+            }
+        }
+
+        Err(self.error_at(line, "unterminated parenthesis"))
+    }
+
+    fn quoted_until(&mut self, quote: char, line: usize) -> Result<(), PkgfileError> {
+        while let Some(c) = self.bump() {
+            if c == quote {
+                return Ok(());
+            }
+            if c == '\\' && quote == '"' {
+                self.bump();
+            }
+        }
+
+        Err(self.error_at(line, "unterminated quote"))
+    }
+
+    /// Reads and expands a shell word until one of `terminators` is found
+    /// outside of quotes.
+    fn word(&mut self, terminators: &[char]) -> Result<String, PkgfileError> {
+        let line = self.line;
+        let mut value = String::new();
+
+        while let Some(c) = self.peek() {
+            if terminators.contains(&c) {
+                break;
+            }
+            self.bump();
+
+            match c {
+                '\'' => {
+                    let start = self.pos;
+                    self.quoted_until('\'', line)?;
+                    value.extend(&self.chars[start..self.pos - 1]);
+                }
+                '"' => value.push_str(&self.double_quoted(line)?),
+                '\\' => match self.bump() {
+                    Some('\n') | None => {}
+                    Some(escaped) => value.push(escaped),
+                },
+                '$' => value.push_str(&self.expansion(line)?),
+                '`' => self.command_substitution('`', line)?,
+                _ => value.push(c),
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn double_quoted(&mut self, line: usize) -> Result<String, PkgfileError> {
+        let mut value = String::new();
+
+        while let Some(c) = self.bump() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match self.bump() {
+                    Some(escaped @ ('"' | '\\' | '$' | '`')) => value.push(escaped),
+                    Some('\n') => {}
+                    Some(other) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                '$' => value.push_str(&self.expansion(line)?),
+                '`' => self.command_substitution('`', line)?,
+                _ => value.push(c),
+            }
+        }
+
+        Err(self.error_at(line, "unterminated quote"))
+    }
+
+    /// Expands `$...`. A value that cannot be evaluated expands to nothing
+    /// and marks the word as unsupported; only malformed text is an error.
+    fn expansion(&mut self, line: usize) -> Result<String, PkgfileError> {
+        let value = match self.peek() {
+            Some('{') => {
+                self.bump();
+                let body = self.braced_body(line)?;
+                self.parameter(&body, line)
+            }
+            Some('(') => {
+                self.command_substitution('(', line)?;
+                return Ok(String::new());
+            }
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                let name = self.read_identifier();
+                self.lookup(&name, line)
+            }
+            _ => return Ok("$".to_string()),
+        };
+
+        Ok(value.unwrap_or_else(|error| {
+            let reason = match error {
+                PkgfileError::Syntax { line, message } => (line, message),
+                error => (line, error.to_string()),
+            };
+            self.unsupported.get_or_insert(reason);
+            String::new()
+        }))
+    }
+
+    /// Skips `$(...)` or a backquoted command, which are never executed, and
+    /// marks the word as unsupported.
+    fn command_substitution(&mut self, start: char, line: usize) -> Result<(), PkgfileError> {
+        if start == '(' {
+            self.skip_parentheses()?;
+        } else {
+            self.quoted_until(start, line)?;
+        }
+        self.unsupported
+            .get_or_insert_with(|| (line, "command substitution is not supported".to_string()));
+        Ok(())
+    }
+
+    fn braced_body(&mut self, line: usize) -> Result<String, PkgfileError> {
+        let mut body = String::new();
+        let mut depth = 1;
+
+        while let Some(c) = self.bump() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                '\n' => return Err(self.error_at(line, "unterminated ${...} expansion")),
+                _ => {}
+            }
+            body.push(c);
+        }
+
+        Err(self.error_at(line, "unterminated ${...} expansion"))
+    }
+
+    fn parameter(&self, body: &str, line: usize) -> Result<String, PkgfileError> {
+        if let Some(name) = body.strip_prefix('#').filter(|name| is_identifier(name)) {
+            return Ok(self.lookup(name, line)?.chars().count().to_string());
+        }
+
+        let name_len = body
+            .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+            .unwrap_or(body.len());
+        let (name, operation) = body.split_at(name_len);
+        if !is_identifier(name) {
+            return Err(self.error_at(line, &format!("bad substitution: ${{{body}}}")));
+        }
+
+        if operation.is_empty() {
+            return self.lookup(name, line);
+        }
+
+        if let Some(default) = operation
+            .strip_prefix(":-")
+            .or_else(|| operation.strip_prefix('-'))
+        {
+            return match self.variables.get(name).filter(|value| !value.is_empty()) {
+                Some(value) => Ok(value.clone()),
+                None => self.expand_text(default, line),
+            };
+        }
+
+        let value = self.lookup(name, line)?;
+        let (op, argument) = split_operator(operation);
+        match op {
+            "##" => Ok(remove_prefix(
+                &value,
+                &self.expand_text(argument, line)?,
+                true,
+            )),
+            "#" => Ok(remove_prefix(
+                &value,
+                &self.expand_text(argument, line)?,
+                false,
+            )),
+            "%%" => Ok(remove_suffix(
+                &value,
+                &self.expand_text(argument, line)?,
+                true,
+            )),
+            "%" => Ok(remove_suffix(
+                &value,
+                &self.expand_text(argument, line)?,
+                false,
+            )),
+            "//" | "/" => {
+                let (pattern, replacement) = split_unescaped(argument, '/');
+                Ok(replace(
+                    &value,
+                    &self.expand_text(pattern, line)?,
+                    &self.expand_text(replacement, line)?,
+                    op == "//",
+                ))
+            }
+            ":" => substring(&value, argument)
+                .ok_or_else(|| self.error_at(line, &format!("bad substitution: ${{{body}}}"))),
+            _ => Err(self.error_at(line, &format!("bad substitution: ${{{body}}}"))),
+        }
+    }
+
+    /// Expands a word that appeared inside `${...}`, using the current variables.
+    fn expand_text(&self, text: &str, line: usize) -> Result<String, PkgfileError> {
+        let mut nested = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            line,
+            variables: self.variables.clone(),
+            skipped: self.skipped.clone(),
+            unsupported: None,
+        };
+        let value = nested.word(&[])?;
+        match nested.unsupported {
+            Some((line, message)) => Err(PkgfileError::Syntax { line, message }),
+            None => Ok(value),
+        }
+    }
+
+    /// The value of a variable; like in the shell, unset variables are empty.
+    fn lookup(&self, name: &str, line: usize) -> Result<String, PkgfileError> {
+        if let Some((skipped_line, reason)) = self.skipped.get(name) {
+            return Err(self.error_at(line, &format!("{name} from line {skipped_line}: {reason}")));
+        }
+        Ok(self.variables.get(name).cloned().unwrap_or_default())
+    }
+
+    fn read_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+        while let Some(c) = self.peek() {
+            let valid = c == '_'
+                || c.is_ascii_alphabetic()
+                || (c.is_ascii_digit() && !identifier.is_empty());
+            if !valid {
+                break;
+            }
+            identifier.push(c);
+            self.bump();
+        }
+        identifier
+    }
+
+    fn skip_blanks_and_comments(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' => {
+                    self.bump();
+                }
+                '#' => self.skip_line(),
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error_at(&self, line: usize, message: &str) -> PkgfileError {
+        PkgfileError::Syntax {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+fn split_operator(operation: &str) -> (&str, &str) {
+    ["##", "%%", "//", "#", "%", "/", ":"]
+        .into_iter()
+        .find_map(|op| operation.strip_prefix(op).map(|rest| (op, rest)))
+        .unwrap_or((operation, ""))
+}
+
+/// Splits `text` at the first `separator` that is not escaped with a
+/// backslash. Escapes are kept; expanding the parts removes them.
+fn split_unescaped(text: &str, separator: char) -> (&str, &str) {
+    let mut chars = text.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == separator {
+            return (&text[..idx], &text[idx + c.len_utf8()..]);
+        }
+    }
+    (text, "")
+}
+
+fn char_boundaries(value: &str) -> Vec<usize> {
+    value
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain(std::iter::once(value.len()))
+        .collect()
+}
+
+fn remove_prefix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut ends = char_boundaries(value);
+    if longest {
+        ends.reverse();
+    }
+
+    ends.into_iter()
+        .find(|&end| glob_match(pattern, &value[..end]))
+        .map_or_else(|| value.to_string(), |end| value[end..].to_string())
+}
+
+fn remove_suffix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut starts = char_boundaries(value);
+    if !longest {
+        starts.reverse();
+    }
+
+    starts
+        .into_iter()
+        .find(|&start| glob_match(pattern, &value[start..]))
+        .map_or_else(|| value.to_string(), |start| value[..start].to_string())
+}
+
+fn replace(value: &str, pattern: &str, replacement: &str, all: bool) -> String {
+    if pattern.is_empty() {
+        return value.to_string();
+    }
+
+    let boundaries = char_boundaries(value);
+    let mut output = String::new();
+    let mut copied = 0;
+    let mut replaced = false;
+
+    for (idx, &start) in boundaries.iter().enumerate() {
+        if start < copied || (replaced && !all) {
+            continue;
+        }
+
+        // Like bash, the longest match at the leftmost position wins
+        let matched_end = boundaries[idx + 1..]
+            .iter()
+            .rev()
+            .find(|&&end| glob_match(pattern, &value[start..end]));
+
+        if let Some(&end) = matched_end {
+            output.push_str(&value[copied..start]);
+            output.push_str(replacement);
+            copied = end;
+            replaced = true;
+        }
+    }
+
+    output.push_str(&value[copied..]);
+    output
+}
+
+fn substring(value: &str, argument: &str) -> Option<String> {
+    let (offset, length) = match argument.split_once(':') {
+        Some((offset, length)) => (offset, Some(length)),
+        None => (argument, None),
+    };
+
+    let chars: Vec<char> = value.chars().collect();
+    let offset = offset.trim().parse::<usize>().ok()?.min(chars.len());
+    let end = match length {
+        Some(length) => (offset + length.trim().parse::<usize>().ok()?).min(chars.len()),
+        None => chars.len(),
+    };
+
+    Some(chars[offset..end].iter().collect())
+}
+
+/// Matches `text` against a shell glob supporting `*`, `?`, `[...]` and `\`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_from(&pattern, &text)
+}
+
+fn glob_match_from(pattern: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        '*' => (0..=text.len()).any(|skip| glob_match_from(rest, &text[skip..])),
+        '?' => !text.is_empty() && glob_match_from(rest, &text[1..]),
+        '[' => match (text.first(), rest.iter().position(|&c| c == ']')) {
+            (Some(&c), Some(close)) if close > 0 => {
+                let class = &rest[..close];
+                let (negated, class) = match class.split_first() {
+                    Some(('!' | '^', class)) => (true, class),
+                    _ => (false, class),
+                };
+                class_contains(class, c) != negated
+                    && glob_match_from(&rest[close + 1..], &text[1..])
+            }
+            (Some(&c), _) => c == '[' && glob_match_from(rest, &text[1..]),
+            (None, _) => false,
+        },
+        '\\' if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match_from(&rest[1..], &text[1..])
+        }
+        _ => text.first() == Some(&first) && glob_match_from(rest, &text[1..]),
+    }
+}
+
+fn class_contains(class: &[char], c: char) -> bool {
+    let mut idx = 0;
+    while idx < class.len() {
+        if idx + 2 < class.len() && class[idx + 1] == '-' {
+            if (class[idx]..=class[idx + 2]).contains(&c) {
+                return true;
+            }
+            idx += 3;
+        } else {
+            if class[idx] == c {
+                return true;
+            }
+            idx += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_of(contents: &str) -> String {
+        let pkgfile = parse_pkgfile(contents).expect("failed to parse Pkgfile");
+        format!("{}-{}", pkgfile.version, pkgfile.release)
+    }
+
+    #[test]
+    fn parse_pkgfile_reads_plain_assignments() {
+        assert_eq!(
+            parse_pkgfile("name=foo\nversion=1.2.3\nrelease=1\n").expect("failed to parse"),
+            Pkgfile {
+                name: Some("foo".to_string()),
+                version: "1.2.3".to_string(),
+                release: "1".to_string(),
+                depends: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_pkgfile_reads_depends_header() {
+        let depends = |contents: &str| parse_pkgfile(contents).expect("failed to parse").depends;
+        assert_eq!(
+            depends("# Description: Foo\n# Depends on: bar, baz  qux\n\nversion=1\nrelease=1\n"),
+            ["bar", "baz", "qux"]
+        );
+        assert!(depends("# Depends on:\nversion=1\nrelease=1\n").is_empty());
+        assert!(depends("version=1\nrelease=1\n# Depends on: bar\n").is_empty());
+    }
+
+    #[test]
+    fn parse_pkgfile_handles_quotes_and_comments() {
+        assert_eq!(version_of("version=\"1.2\"\nrelease='3'\n"), "1.2-3");
+        assert_eq!(
+            version_of("version=1.2 # upstream tag\nrelease=1\n"),
+            "1.2-1"
+        );
+        assert_eq!(version_of("version=1.2#3\nrelease=1\n"), "1.2#3-1");
+    }
+
+    #[test]
+    fn parse_pkgfile_expands_variables() {
+        assert_eq!(
+            version_of("_major=6\n_minor=1\nversion=${_major}.$_minor\nrelease=1\n"),
+            "6.1-1"
+        );
+        assert_eq!(
+            version_of("_base=2.0\nversion=\"${_base}.5\"\nrelease=2\n"),
+            "2.0.5-2"
+        );
+    }
+
+    #[test]
+    fn parse_pkgfile_supports_parameter_substitutions() {
+        assert_eq!(
+            version_of("_v=1_2_3\nversion=${_v//_/.}\nrelease=1\n"),
+            "1.2.3-1"
+        );
+        assert_eq!(
+            version_of("_v=2024/01/02\nversion=${_v//\\//.}\nrelease=1\n"),
+            "2024.01.02-1"
+        );
+        assert_eq!(
+            version_of("_v=1.2.3\nversion=${_v/./\\/}\nrelease=1\n"),
+            "1/2.3-1"
+        );
+        assert_eq!(
+            version_of("_v=v1.2.3\nversion=${_v#v}\nrelease=1\n"),
+            "1.2.3-1"
+        );
+        assert_eq!(
+            version_of("_v=1.2.3\nversion=${_v%.*}\nrelease=1\n"),
+            "1.2-1"
+        );
+        assert_eq!(
+            version_of("_v=1.2.3\nversion=${_v%%.*}\nrelease=1\n"),
+            "1-1"
+        );
+        assert_eq!(
+            version_of("_v=1.2.3\nversion=${_v##*.}\nrelease=1\n"),
+            "3-1"
+        );
+        assert_eq!(
+            version_of("_v=20240101\nversion=${_v:0:4}\nrelease=${_rel:-1}\n"),
+            "2024-1"
+        );
+    }
+
+    #[test]
+    fn parse_pkgfile_skips_arrays_and_stops_at_build() {
+        assert_eq!(
+            version_of(
+                "\
+version=1.0
+release=1
+source=(https://example.org/$name-$version.tar.gz
+        fix.patch)
+
+build() {
+    version=9.9
+}
+"
+            ),
+            "1.0-1"
+        );
+    }
+
+    #[test]
+    fn parse_pkgfile_skips_assignments_it_cannot_evaluate() {
+        assert_eq!(
+            version_of(
+                "\
+_date=$(date +%Y%m%d)
+_snapshot=${_date}-`git rev-parse HEAD`
+_url=https://example.org/$_pkgname/${_snapshot}
+version=1.0${_suffix}
+release=1
+"
+            ),
+            "1.0-1"
+        );
+    }
+
+    #[test]
+    fn parse_pkgfile_reports_unparsable_files() {
+        assert!(matches!(
+            parse_pkgfile("version=$(cat VERSION)\nrelease=1\n"),
+            Err(PkgfileError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_pkgfile("_v=`cat VERSION`\nrelease=1\nversion=${_v%.*}\n"),
+            Err(PkgfileError::Syntax { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse_pkgfile("release=1\nversion=${_missing}\n"),
+            Err(PkgfileError::Missing("version"))
+        ));
+        assert!(matches!(
+            parse_pkgfile("version=\"1.0\nrelease=1\n"),
+            Err(PkgfileError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_pkgfile("version=1.0\n"),
+            Err(PkgfileError::Missing("release"))
+        ));
+    }
+
+    #[test]
+    fn glob_match_supports_wildcards_and_classes() {
+        assert!(glob_match("*.tar.*", "foo.tar.gz"));
+        assert!(glob_match("v?", "v1"));
+        assert!(glob_match("[0-9]*", "1abc"));
+        assert!(!glob_match("[!0-9]*", "1abc"));
+        assert!(!glob_match("*.zip", "foo.tar.gz"));
+    }
+}
@@ -0,0 +1,204 @@
+//! The dependency graph of installed packages: orders pending updates so
+//! that every package is built after the updated packages it depends on, and
+//! finds the packages an update may require rebuilding.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The installed dependencies of each installed package, keyed by package
+/// name.
+pub type Dependencies = HashMap<String, Vec<String>>;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// The pending packages in build order.
+    pub order: Vec<String>,
+    /// Dependency cycles involving a pending package, each starting and
+    /// ending with the same package.
+    pub cycles: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Copy)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+struct Walk<'a> {
+    dependencies: &'a Dependencies,
+    marks: HashMap<&'a str, Mark>,
+    stack: Vec<&'a str>,
+    order: Vec<&'a str>,
+    cycles: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Walk<'a> {
+    fn visit(&mut self, name: &'a str) {
+        match self.marks.get(name) {
+            Some(Mark::Done) => return,
+            Some(Mark::Visiting) => {
+                let start = self.stack.iter().rposition(|&entry| entry == name);
+                let mut cycle = self.stack[start.unwrap_or_default()..].to_vec();
+                cycle.push(name);
+                self.cycles.push(cycle);
+                return;
+            }
+            None => {}
+        }
+
+        self.marks.insert(name, Mark::Visiting);
+        self.stack.push(name);
+        let dependencies = self.dependencies;
+        for dependency in dependencies.get(name).into_iter().flatten() {
+            self.visit(dependency);
+        }
+        self.stack.pop();
+        self.marks.insert(name, Mark::Done);
+        self.order.push(name);
+    }
+}
+
+/// Sorts `pending` so that dependencies come first. Packages without an
+/// ordering constraint keep their relative order; a cycle is broken at the
+/// dependency that closes it.
+pub fn build_order(pending: &[String], dependencies: &Dependencies) -> Plan {
+    let mut walk = Walk {
+        dependencies,
+        marks: HashMap::new(),
+        stack: Vec::new(),
+        order: Vec::new(),
+        cycles: Vec::new(),
+    };
+    for name in pending {
+        walk.visit(name);
+    }
+
+    let is_pending = |name: &&str| pending.iter().any(|pending| pending == name);
+    let owned = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+    Plan {
+        order: owned(
+            &walk
+                .order
+                .into_iter()
+                .filter(is_pending)
+                .collect::<Vec<_>>(),
+        ),
+        cycles: walk
+            .cycles
+            .iter()
+            .filter(|cycle| cycle.iter().any(is_pending))
+            .map(|cycle| owned(cycle))
+            .collect(),
+    }
+}
+
+/// Inverts `dependencies`, mapping each package to the installed packages
+/// that depend on it directly, sorted by name.
+pub fn dependents(dependencies: &Dependencies) -> Dependencies {
+    let mut dependents = Dependencies::new();
+    for (name, depends) in dependencies {
+        for dependency in depends {
+            dependents
+                .entry(dependency.clone())
+                .or_default()
+                .push(name.clone());
+        }
+    }
+    for names in dependents.values_mut() {
+        names.sort_unstable();
+        names.dedup();
+    }
+    dependents
+}
+
+/// Lists the packages depending on `name` directly or through other
+/// packages, nearest first. Each one is paired with the dependency it was
+/// reached through.
+pub fn reverse_dependencies(name: &str, dependents: &Dependencies) -> Vec<(String, String)> {
+    let mut seen = HashSet::from([name]);
+    let mut queue = VecDeque::from([name]);
+    let mut found = Vec::new();
+
+    while let Some(dependency) = queue.pop_front() {
+        for dependent in dependents.get(dependency).into_iter().flatten() {
+            if seen.insert(dependent) {
+                queue.push_back(dependent);
+                found.push((dependent.clone(), dependency.to_string()));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn dependencies(edges: &[(&str, &[&str])]) -> Dependencies {
+        edges
+            .iter()
+            .map(|(name, depends)| (name.to_string(), names(depends)))
+            .collect()
+    }
+
+    #[test]
+    fn build_order_puts_dependencies_first() {
+        let dependencies = dependencies(&[("app", &["lib", "zlib"]), ("lib", &["zlib"])]);
+        assert_eq!(
+            build_order(&names(&["app", "other", "lib", "zlib"]), &dependencies),
+            Plan {
+                order: names(&["zlib", "lib", "app", "other"]),
+                cycles: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn build_order_follows_installed_packages_without_updates() {
+        // glib is installed but up to date, so only its dependency moves
+        let dependencies = dependencies(&[("gtk", &["glib"]), ("glib", &["pcre2"])]);
+        assert_eq!(
+            build_order(&names(&["gtk", "pcre2"]), &dependencies).order,
+            names(&["pcre2", "gtk"])
+        );
+    }
+
+    #[test]
+    fn build_order_reports_cycles() {
+        let dependencies = dependencies(&[
+            ("freetype", &["harfbuzz"]),
+            ("harfbuzz", &["freetype"]),
+            ("idle", &["loop"]),
+            ("loop", &["loop"]),
+        ]);
+        let plan = build_order(&names(&["freetype", "harfbuzz"]), &dependencies);
+        assert_eq!(plan.order, names(&["harfbuzz", "freetype"]));
+        assert_eq!(plan.cycles, [names(&["freetype", "harfbuzz", "freetype"])]);
+    }
+
+    #[test]
+    fn reverse_dependencies_walks_dependents_nearest_first() {
+        let dependents = dependents(&dependencies(&[
+            ("curl", &["openssl"]),
+            ("git", &["curl", "openssl"]),
+            ("python3", &["openssl"]),
+            ("meson", &["python3"]),
+            ("zstd", &[]),
+        ]));
+        let pair = |dependent: &str, via: &str| (dependent.to_string(), via.to_string());
+
+        assert_eq!(
+            reverse_dependencies("openssl", &dependents),
+            [
+                pair("curl", "openssl"),
+                pair("git", "openssl"),
+                pair("python3", "openssl"),
+                pair("meson", "python3"),
+            ]
+        );
+        assert!(reverse_dependencies("zstd", &dependents).is_empty());
+    }
+}
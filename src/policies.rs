@@ -0,0 +1,72 @@
+use crate::version::Comparison;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Version comparisons that replace `--compare` for single packages, keyed
+/// by package name.
+pub type Policies = HashMap<String, Comparison>;
+
+pub static POLICIES_FILE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    xdg::BaseDirectories::with_prefix("scun")
+        .place_config_file("compare.conf")
+        .expect("Failed to create comparison policy path")
+});
+
+/// Reads `package policy` lines, e.g. `openssl p-is-patch`. Lines that
+/// cannot be parsed are returned as warnings.
+pub fn read_policies(path: &Path) -> io::Result<(Policies, Vec<String>)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+        Err(e) => return Err(e),
+    };
+
+    let mut policies = Policies::new();
+    let mut warnings = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        match parse_policy_line(&line?) {
+            Ok(Some((package, comparison))) => {
+                policies.insert(package, comparison);
+            }
+            Ok(None) => {}
+            Err(e) => warnings.push(format!("{}:{}: {e}", path.display(), idx + 1)),
+        }
+    }
+
+    Ok((policies, warnings))
+}
+
+fn parse_policy_line(line: &str) -> Result<Option<(String, Comparison)>, String> {
+    let line = line.split('#').next().unwrap_or_default();
+    let mut fields = line.split_whitespace();
+    let Some(package) = fields.next() else {
+        return Ok(None);
+    };
+
+    match (fields.next(), fields.next()) {
+        (Some(policy), None) => Ok(Some((package.to_string(), policy.parse()?))),
+        (None, _) => Err(format!("missing policy for {package}")),
+        (Some(_), Some(extra)) => Err(format!("unexpected '{extra}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_policy_line_reads_package_and_policy() {
+        assert_eq!(
+            parse_policy_line("openssl  p-is-patch # 1.1.1w is a patch"),
+            Ok(Some(("openssl".to_string(), Comparison::PIsPatch)))
+        );
+        assert_eq!(parse_policy_line("# comment"), Ok(None));
+        assert_eq!(parse_policy_line(""), Ok(None));
+        assert!(parse_policy_line("foo").is_err());
+        assert!(parse_policy_line("foo newest").is_err());
+        assert!(parse_policy_line("foo string extra").is_err());
+    }
+}
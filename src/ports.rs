@@ -1,6 +1,11 @@
 use crate::cache::{
     is_cache_valid, read_cache_from_file, save_cache_to_file, CacheError, CACHE_FILE_PATH,
 };
+use crate::footprint::is_versioned_library;
+use crate::pkgfile::{read_pkgfile, Pkgfile, PkgfileError};
+use crate::version::Comparison;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
@@ -9,58 +14,190 @@ use std::time::UNIX_EPOCH;
 
 pub type PackageInfo = (String, Option<String>);
 
+/// Installed files of each package, as listed in the package database.
+pub type InstalledFiles = HashMap<String, Vec<String>>;
+
+/// What the package database records about the installed packages.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Installed {
+    pub packages: Vec<PackageInfo>,
+    /// The versioned shared libraries of each package, the only installed
+    /// files the update check looks at.
+    pub libraries: InstalledFiles,
+}
+
+/// A package database entry: name, version and the installed files.
+type PkgDbEntry = (String, String, Vec<String>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repository {
+    pub path: PathBuf,
+    /// The ports a `prtdir` line restricts the collection to.
+    pub packages: Option<Vec<String>>,
+}
+
+impl Repository {
+    fn provides(&self, package_name: &str) -> bool {
+        self.packages
+            .as_ref()
+            .is_none_or(|packages| packages.iter().any(|package| package == package_name))
+    }
+}
+
 const PKG_DB_PATH: &str = "/var/lib/pkg/db";
 const PRT_GET_CONF_PATH: &str = "/etc/prt-get.conf";
+const PRT_GET_ALIASES_PATH: &str = "/etc/prt-get.aliases";
+const PRT_GET_LOCKER_PATH: &str = "/var/lib/pkg/prt-get.locker";
 
-static REPO_PATHS: LazyLock<Vec<PathBuf>> =
-    LazyLock::new(|| match read_repository_paths(PRT_GET_CONF_PATH) {
-        Ok(paths) => paths,
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PrtGetConfig {
+    repositories: Vec<Repository>,
+    prefer_higher: bool,
+}
+
+static PRT_GET_CONFIG: LazyLock<PrtGetConfig> =
+    LazyLock::new(|| match read_prt_get_config(PRT_GET_CONF_PATH) {
+        Ok(config) => config,
         Err(e) => {
             eprintln!("Failed to read {PRT_GET_CONF_PATH}: {e}");
-            Vec::new()
+            PrtGetConfig::default()
         }
     });
 
-pub static INSTALLED_PACKAGES: LazyLock<Vec<PackageInfo>> = LazyLock::new(|| {
-    let db_mod_time = match pkg_db_mod_time() {
-        Ok(db_mod_time) => db_mod_time,
+/// Maps installed package names to the port they are an alias for.
+static PACKAGE_ALIASES: LazyLock<HashMap<String, String>> =
+    LazyLock::new(|| match read_package_aliases(PRT_GET_ALIASES_PATH) {
+        Ok(aliases) => aliases,
+        Err(CacheError::Io(e)) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
         Err(e) => {
-            eprintln!("Could not read package database metadata: {e}");
-            return Vec::new();
+            eprintln!("Failed to read {PRT_GET_ALIASES_PATH}: {e}");
+            HashMap::new()
         }
-    };
+    });
+
+/// Reads the prt-get lock list. It is read on every call so that resident
+/// modes notice `prt-get lock` and `prt-get unlock`.
+pub fn locked_packages() -> HashSet<String> {
+    match read_locked_packages(PRT_GET_LOCKER_PATH) {
+        Ok(packages) => packages,
+        Err(CacheError::Io(e)) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+        Err(e) => {
+            eprintln!("Failed to read {PRT_GET_LOCKER_PATH}: {e}");
+            HashSet::new()
+        }
+    }
+}
+
+/// Lists installed packages and their libraries, only parsing the package
+/// database when it changed since the cache was written.
+pub fn installed_packages() -> Result<Installed, CacheError> {
+    let db_mod_time = pkg_db_mod_time()?;
 
     match read_cache_from_file(&CACHE_FILE_PATH) {
-        Ok(contents) if is_cache_valid(&contents, db_mod_time) => contents.data,
-        _ => fetch_installed_packages(db_mod_time).unwrap_or_else(|e| {
-            eprintln!("Error fetching installed packages: {e}");
-            Vec::new()
+        Ok(contents) if is_cache_valid(&contents, db_mod_time) => Ok(Installed {
+            packages: contents.data,
+            libraries: contents.libraries.unwrap_or_default(),
         }),
+        _ => fetch_installed_packages(db_mod_time),
     }
-});
+}
 
-fn read_repository_paths(path: &str) -> Result<Vec<PathBuf>, CacheError> {
+fn read_prt_get_config(path: &str) -> Result<PrtGetConfig, CacheError> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
 
-    reader.lines().try_fold(Vec::new(), |mut paths, line| {
-        if let Some(path) = parse_repository_path(&line?) {
-            paths.push(path);
-        }
-        Ok(paths)
-    })
+    reader
+        .lines()
+        .try_fold(PrtGetConfig::default(), |mut config, line| {
+            let line = line?;
+            if let Some(repository) = parse_repository_path(&line) {
+                config.repositories.push(repository);
+            } else if let Some(prefer_higher) = parse_prefer_higher(&line) {
+                config.prefer_higher = prefer_higher;
+            }
+            Ok(config)
+        })
 }
 
-fn parse_repository_path(line: &str) -> Option<PathBuf> {
+fn parse_prefer_higher(line: &str) -> Option<bool> {
     let line = line.split('#').next()?.trim();
     let mut fields = line.split_whitespace();
 
     match (fields.next(), fields.next()) {
-        (Some("prtdir"), Some(path)) => Some(PathBuf::from(path)),
+        (Some("preferhigher"), Some("yes")) => Some(true),
+        (Some("preferhigher"), Some("no")) => Some(false),
         _ => None,
     }
 }
 
+fn parse_repository_path(line: &str) -> Option<Repository> {
+    let line = line.split('#').next()?.trim();
+    let (key, value) = line.split_once(char::is_whitespace)?;
+    if key != "prtdir" {
+        return None;
+    }
+
+    // prt-get allows restricting a collection with `prtdir <path>:<port>,<port>`
+    let (path, packages) = match value.split_once(':') {
+        Some((path, list)) => (
+            path,
+            Some(
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|package| !package.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            ),
+        ),
+        None => (value, None),
+    };
+
+    let path = path.trim();
+    (!path.is_empty()).then(|| Repository {
+        path: PathBuf::from(path),
+        packages,
+    })
+}
+
+fn read_package_aliases(path: &str) -> Result<HashMap<String, String>, CacheError> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    reader
+        .lines()
+        .try_fold(HashMap::new(), |mut aliases, line| {
+            if let Some((alias, port)) = parse_alias_line(&line?) {
+                aliases.insert(alias, port);
+            }
+            Ok(aliases)
+        })
+}
+
+/// Parses an `original: alias` line, returning `(alias, original)`.
+fn parse_alias_line(line: &str) -> Option<(String, String)> {
+    let line = line.split('#').next()?.trim();
+    let (port, alias) = line.split_once(':')?;
+    let (port, alias) = (port.trim(), alias.trim());
+
+    (!port.is_empty() && !alias.is_empty()).then(|| (alias.to_string(), port.to_string()))
+}
+
+fn read_locked_packages(path: &str) -> Result<HashSet<String>, CacheError> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    reader
+        .lines()
+        .try_fold(HashSet::new(), |mut packages, line| {
+            let line = line?;
+            let package = line.trim();
+            if !package.is_empty() {
+                packages.insert(package.to_string());
+            }
+            Ok(packages)
+        })
+}
+
 fn pkg_db_mod_time() -> Result<u64, CacheError> {
     Ok(fs::metadata(PKG_DB_PATH)?
         .modified()?
@@ -68,48 +205,179 @@ fn pkg_db_mod_time() -> Result<u64, CacheError> {
         .as_secs())
 }
 
-fn fetch_installed_packages(db_mod_time: u64) -> Result<Vec<PackageInfo>, CacheError> {
-    let packages = list_installed_packages(PKG_DB_PATH)?;
-    save_cache_to_file(&CACHE_FILE_PATH, &packages, db_mod_time)?;
-    Ok(packages)
+fn fetch_installed_packages(db_mod_time: u64) -> Result<Installed, CacheError> {
+    let installed = list_installed_packages(PKG_DB_PATH)?;
+    save_cache_to_file(
+        &CACHE_FILE_PATH,
+        &installed.packages,
+        &installed.libraries,
+        db_mod_time,
+    )?;
+    Ok(installed)
+}
+
+/// The directory holding the package database and prt-get's lock list.
+pub fn pkg_db_dir() -> &'static Path {
+    Path::new(PKG_DB_PATH).parent().unwrap_or(Path::new("/"))
+}
+
+/// The collections of prt-get.conf, in `prtdir` order.
+pub fn repositories() -> &'static [Repository] {
+    &PRT_GET_CONFIG.repositories
+}
+
+pub fn prt_get_prefers_higher() -> bool {
+    PRT_GET_CONFIG.prefer_higher
 }
 
-pub fn find_ports_in_repositories(package_name: &str) -> Option<PathBuf> {
-    find_port_in_repositories(package_name, &REPO_PATHS)
+pub fn aliased_port(package_name: &str) -> Option<&'static str> {
+    PACKAGE_ALIASES.get(package_name).map(String::as_str)
 }
 
-fn find_port_in_repositories(package_name: &str, repo_paths: &[PathBuf]) -> Option<PathBuf> {
+/// What a collection has to offer for a package.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PortLookup {
+    /// The `prtdir` line restricts the collection to other ports.
+    Filtered,
+    Missing,
+    Found(PathBuf),
+}
+
+/// Looks a package up in every collection, in `prtdir` order.
+pub fn lookup_port<'a>(
+    package_name: &str,
+    repo_paths: &'a [Repository],
+) -> Vec<(&'a Path, PortLookup)> {
     repo_paths
         .iter()
-        .map(|repo_path| repo_path.join(package_name))
-        .find(|port_dir| port_dir.is_dir())
+        .map(|repo| {
+            let port_dir = repo.path.join(package_name);
+            let lookup = if !repo.provides(package_name) {
+                PortLookup::Filtered
+            } else if port_dir.is_dir() {
+                PortLookup::Found(port_dir)
+            } else {
+                PortLookup::Missing
+            };
+            (repo.path.as_path(), lookup)
+        })
+        .collect()
 }
 
-pub fn extract_pkgfile_version(port_dir: &Path) -> Option<String> {
-    let pkgfile_path = port_dir.join("Pkgfile");
-    let file = File::open(pkgfile_path).ok()?;
-    let reader = BufReader::new(file);
+/// Lists the port directories of a package in every collection providing
+/// it, in `prtdir` order.
+pub fn port_dirs<'a>(
+    package_name: &'a str,
+    repo_paths: &'a [Repository],
+) -> impl Iterator<Item = PathBuf> + 'a {
+    repo_paths
+        .iter()
+        .filter(move |repo| repo.provides(package_name))
+        .map(move |repo| repo.path.join(package_name))
+        .filter(|port_dir| port_dir.is_dir())
+}
 
-    let mut version: Option<String> = None;
-    let mut release: Option<String> = None;
+/// Finds the port of a package in the first collection providing it, or
+/// with `prefer_higher` the one with the highest version by `comparison`.
+pub fn find_port_in_repositories(
+    package_name: &str,
+    repo_paths: &[Repository],
+    prefer_higher: bool,
+    comparison: Comparison,
+) -> Option<PathBuf> {
+    let mut port_dirs = port_dirs(package_name, repo_paths);
+
+    if !prefer_higher {
+        return port_dirs.next();
+    }
+
+    // On equal versions the collection listed first keeps precedence
+    port_dirs
+        .map(|port_dir| {
+            let version = extract_pkgfile_version(&port_dir).ok();
+            (port_dir, version)
+        })
+        .reduce(|best, candidate| {
+            if is_higher_version(candidate.1.as_deref(), best.1.as_deref(), comparison) {
+                candidate
+            } else {
+                best
+            }
+        })
+        .map(|(port_dir, _)| port_dir)
+}
 
-    for line in reader.lines().map_while(Result::ok) {
-        let line = line.trim_start();
-        if let Some(value) = line.strip_prefix("version=").map(str::trim) {
-            version = Some(value.to_string());
-        } else if let Some(value) = line.strip_prefix("release=").map(str::trim) {
-            release = Some(value.to_string());
+fn is_higher_version(
+    candidate: Option<&str>,
+    current: Option<&str>,
+    comparison: Comparison,
+) -> bool {
+    match (candidate, current) {
+        (Some(candidate), Some(current)) => {
+            comparison.compare(candidate, current) == Some(Ordering::Greater)
         }
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
 
-        if version.is_some() && release.is_some() {
-            break;
+/// Reads the Pkgfile of a port, rejecting it if its `name` does not match
+/// the port directory.
+pub fn read_port_pkgfile(port_dir: &Path) -> Result<Pkgfile, PkgfileError> {
+    let pkgfile = read_pkgfile(&port_dir.join("Pkgfile"))?;
+    let port = port_dir.file_name().map(|port| port.to_string_lossy());
+
+    match (&pkgfile.name, port) {
+        (Some(name), Some(port)) if *name != port => Err(PkgfileError::NameMismatch {
+            port: port.into_owned(),
+            name: name.clone(),
+        }),
+        _ => Ok(pkgfile),
+    }
+}
+
+pub fn extract_pkgfile_version(port_dir: &Path) -> Result<String, PkgfileError> {
+    Ok(read_port_pkgfile(port_dir)?.version_release())
+}
+
+/// The ports listed in the `# Depends on:` header of a port's Pkgfile.
+pub fn extract_pkgfile_depends(port_dir: &Path) -> Result<Vec<String>, PkgfileError> {
+    Ok(read_pkgfile(&port_dir.join("Pkgfile"))?.depends)
+}
+
+fn list_installed_packages(filename: &str) -> Result<Installed, CacheError> {
+    let mut installed = Installed::default();
+    for (name, version, libraries) in read_pkg_db(filename, |_, path| is_versioned_library(path))? {
+        if !libraries.is_empty() {
+            installed.libraries.insert(name.clone(), libraries);
         }
+        installed.packages.push((name, Some(version)));
     }
+    Ok(installed)
+}
+
+/// Reads the installed files of `packages` from the package database.
+pub fn installed_files(packages: &HashSet<&str>) -> Result<InstalledFiles, CacheError> {
+    read_installed_files(PKG_DB_PATH, packages)
+}
 
-    version.and_then(|v| release.map(|r| format!("{v}-{r}")))
+fn read_installed_files(
+    filename: &str,
+    packages: &HashSet<&str>,
+) -> Result<InstalledFiles, CacheError> {
+    Ok(read_pkg_db(filename, |name, _| packages.contains(name))?
+        .into_iter()
+        .filter(|(name, _, _)| packages.contains(name.as_str()))
+        .map(|(name, _, files)| (name, files))
+        .collect())
 }
 
-fn list_installed_packages(filename: &str) -> Result<Vec<PackageInfo>, CacheError> {
+/// Reads the package database, only keeping the installed files that
+/// `keep_file` selects for a package.
+fn read_pkg_db(
+    filename: &str,
+    keep_file: impl Fn(&str, &str) -> bool,
+) -> Result<Vec<PkgDbEntry>, CacheError> {
     let file = File::open(filename)?;
     let reader = BufReader::new(file);
 
@@ -121,8 +389,8 @@ fn list_installed_packages(filename: &str) -> Result<Vec<PackageInfo>, CacheErro
             break;
         };
 
-        packages.push((name, Some(version)));
-        skip_footprint(&mut lines)?;
+        let files = read_file_list(&mut lines, |path| keep_file(&name, path))?;
+        packages.push((name, version, files));
     }
 
     Ok(packages)
@@ -142,21 +410,28 @@ where
     Ok(None)
 }
 
-fn skip_footprint<I>(lines: &mut I) -> Result<(), CacheError>
+/// Reads the file list of a package database entry up to the blank line
+/// ending it, collecting the files `keep` selects.
+fn read_file_list<I>(lines: &mut I, keep: impl Fn(&str) -> bool) -> Result<Vec<String>, CacheError>
 where
     I: Iterator<Item = io::Result<String>>,
 {
+    let mut files = Vec::new();
     for line in lines.by_ref() {
-        if line?.trim().is_empty() {
+        let line = line?;
+        if line.trim().is_empty() {
             break;
         }
+        if keep(&line) {
+            files.push(line);
+        }
     }
 
-    Ok(())
+    Ok(files)
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use std::env;
     use std::fs;
@@ -164,12 +439,12 @@ mod tests {
     use std::process;
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    struct TestDir {
+    pub(crate) struct TestDir {
         path: PathBuf,
     }
 
     impl TestDir {
-        fn new(name: &str) -> Self {
+        pub(crate) fn new(name: &str) -> Self {
             let nanos = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("test time is before unix epoch")
@@ -179,7 +454,7 @@ mod tests {
             Self { path }
         }
 
-        fn path(&self) -> &Path {
+        pub(crate) fn path(&self) -> &Path {
             &self.path
         }
     }
@@ -190,24 +465,52 @@ mod tests {
         }
     }
 
-    fn write_file(path: &Path, contents: &str) {
+    pub(crate) fn write_file(path: &Path, contents: &str) {
         let mut file = File::create(path).expect("failed to create test file");
         file.write_all(contents.as_bytes())
             .expect("failed to write test file");
     }
 
+    pub(crate) fn repository(path: impl Into<PathBuf>) -> Repository {
+        Repository {
+            path: path.into(),
+            packages: None,
+        }
+    }
+
+    fn filtered_repository(path: impl Into<PathBuf>, packages: &[&str]) -> Repository {
+        Repository {
+            path: path.into(),
+            packages: Some(packages.iter().map(|p| p.to_string()).collect()),
+        }
+    }
+
     #[test]
     fn parse_repository_path_reads_prtdir_lines() {
         assert_eq!(
             parse_repository_path("  prtdir /usr/ports/core # comment"),
-            Some(PathBuf::from("/usr/ports/core"))
+            Some(repository("/usr/ports/core"))
         );
         assert_eq!(parse_repository_path("runscript /usr/ports/core"), None);
         assert_eq!(parse_repository_path("# prtdir /usr/ports/core"), None);
+        assert_eq!(parse_repository_path("prtdirs /usr/ports/core"), None);
+    }
+
+    #[test]
+    fn parse_repository_path_reads_package_filters() {
+        assert_eq!(
+            parse_repository_path("prtdir /usr/ports/foo:pkg1,pkg2"),
+            Some(filtered_repository("/usr/ports/foo", &["pkg1", "pkg2"]))
+        );
+        assert_eq!(
+            parse_repository_path("prtdir /usr/ports/foo: pkg1, pkg2 # comment"),
+            Some(filtered_repository("/usr/ports/foo", &["pkg1", "pkg2"]))
+        );
+        assert_eq!(parse_repository_path("prtdir :pkg1"), None);
     }
 
     #[test]
-    fn read_repository_paths_reads_only_prtdir_entries() {
+    fn read_prt_get_config_reads_prtdir_and_preferhigher_entries() {
         let temp = TestDir::new("prt-get-conf");
         let conf = temp.path().join("prt-get.conf");
         write_file(
@@ -216,16 +519,74 @@ mod tests {
 prtdir /usr/ports/core
 runscript /usr/ports/core
 prtdir /usr/ports/contrib # comment
+preferhigher yes
 ",
         );
 
         assert_eq!(
-            read_repository_paths(conf.to_str().expect("test path is not valid utf-8"))
-                .expect("failed to read repository paths"),
-            vec![
-                PathBuf::from("/usr/ports/core"),
-                PathBuf::from("/usr/ports/contrib")
-            ]
+            read_prt_get_config(conf.to_str().expect("test path is not valid utf-8"))
+                .expect("failed to read prt-get config"),
+            PrtGetConfig {
+                repositories: vec![
+                    repository("/usr/ports/core"),
+                    repository("/usr/ports/contrib")
+                ],
+                prefer_higher: true,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_prefer_higher_reads_yes_and_no() {
+        assert_eq!(parse_prefer_higher("preferhigher yes"), Some(true));
+        assert_eq!(
+            parse_prefer_higher("  preferhigher no # default"),
+            Some(false)
+        );
+        assert_eq!(parse_prefer_higher("# preferhigher yes"), None);
+        assert_eq!(parse_prefer_higher("preferhigher maybe"), None);
+    }
+
+    #[test]
+    fn parse_alias_line_maps_alias_to_original_port() {
+        assert_eq!(
+            parse_alias_line("openssl: libressl # comment"),
+            Some(("libressl".to_string(), "openssl".to_string()))
+        );
+        assert_eq!(parse_alias_line("# openssl: libressl"), None);
+        assert_eq!(parse_alias_line("openssl:"), None);
+        assert_eq!(parse_alias_line("openssl libressl"), None);
+    }
+
+    #[test]
+    fn read_package_aliases_reads_all_entries() {
+        let temp = TestDir::new("prt-get-aliases");
+        let aliases = temp.path().join("prt-get.aliases");
+        write_file(
+            &aliases,
+            "# original: alias\nopenssl: libressl\njdk: openjdk\n",
+        );
+
+        assert_eq!(
+            read_package_aliases(aliases.to_str().expect("test path is not valid utf-8"))
+                .expect("failed to read package aliases"),
+            HashMap::from([
+                ("libressl".to_string(), "openssl".to_string()),
+                ("openjdk".to_string(), "jdk".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn read_locked_packages_reads_one_package_per_line() {
+        let temp = TestDir::new("prt-get-locker");
+        let locker = temp.path().join("prt-get.locker");
+        write_file(&locker, "linux\n\n  mesa  \n");
+
+        assert_eq!(
+            read_locked_packages(locker.to_str().expect("test path is not valid utf-8"))
+                .expect("failed to read locked packages"),
+            HashSet::from(["linux".to_string(), "mesa".to_string()])
         );
     }
 
@@ -238,17 +599,153 @@ prtdir /usr/ports/contrib # comment
         fs::create_dir_all(first_repo.join("bar")).expect("failed to create first bar");
         fs::create_dir_all(second_repo.join("foo")).expect("failed to create second foo");
 
-        let repo_paths = [first_repo.clone(), second_repo.clone()];
+        let repo_paths = [repository(&first_repo), repository(&second_repo)];
 
         assert_eq!(
-            find_port_in_repositories("foo", &repo_paths),
+            find_port_in_repositories("foo", &repo_paths, false, Comparison::Libversion),
             Some(first_repo.join("foo"))
         );
         assert_eq!(
-            find_port_in_repositories("bar", &repo_paths),
+            find_port_in_repositories("bar", &repo_paths, false, Comparison::Libversion),
             Some(first_repo.join("bar"))
         );
-        assert_eq!(find_port_in_repositories("missing", &repo_paths), None);
+        assert_eq!(
+            find_port_in_repositories("missing", &repo_paths, false, Comparison::Libversion),
+            None
+        );
+    }
+
+    #[test]
+    fn find_port_in_repositories_honors_package_filters() {
+        let temp = TestDir::new("ports-filter");
+        let overlay = temp.path().join("overlay");
+        let fallback = temp.path().join("fallback");
+        fs::create_dir_all(overlay.join("foo")).expect("failed to create overlay foo");
+        fs::create_dir_all(overlay.join("bar")).expect("failed to create overlay bar");
+        fs::create_dir_all(fallback.join("bar")).expect("failed to create fallback bar");
+
+        let repo_paths = [
+            filtered_repository(&overlay, &["foo"]),
+            repository(&fallback),
+        ];
+
+        assert_eq!(
+            find_port_in_repositories("foo", &repo_paths, false, Comparison::Libversion),
+            Some(overlay.join("foo"))
+        );
+        assert_eq!(
+            find_port_in_repositories("bar", &repo_paths, false, Comparison::Libversion),
+            Some(fallback.join("bar"))
+        );
+    }
+
+    #[test]
+    fn port_dirs_lists_every_providing_collection_in_order() {
+        let temp = TestDir::new("ports-all");
+        let overlay = temp.path().join("overlay");
+        let opt = temp.path().join("opt");
+        let contrib = temp.path().join("contrib");
+        fs::create_dir_all(overlay.join("foo")).expect("failed to create overlay foo");
+        fs::create_dir_all(opt.join("foo")).expect("failed to create opt foo");
+        fs::create_dir_all(contrib.join("foo")).expect("failed to create contrib foo");
+
+        let repo_paths = [
+            repository(&overlay),
+            filtered_repository(&opt, &["bar"]),
+            repository(&contrib),
+        ];
+
+        assert_eq!(
+            port_dirs("foo", &repo_paths).collect::<Vec<_>>(),
+            vec![overlay.join("foo"), contrib.join("foo")]
+        );
+        assert_eq!(port_dirs("bar", &repo_paths).count(), 0);
+    }
+
+    #[test]
+    fn lookup_port_reports_every_collection() {
+        let temp = TestDir::new("ports-lookup-all");
+        let overlay = temp.path().join("overlay");
+        let opt = temp.path().join("opt");
+        let contrib = temp.path().join("contrib");
+        fs::create_dir_all(opt.join("foo")).expect("failed to create opt foo");
+        fs::create_dir_all(contrib.join("foo")).expect("failed to create contrib foo");
+
+        let repo_paths = [
+            repository(&overlay),
+            repository(&opt),
+            filtered_repository(&contrib, &["bar"]),
+        ];
+
+        assert_eq!(
+            lookup_port("foo", &repo_paths),
+            vec![
+                (overlay.as_path(), PortLookup::Missing),
+                (opt.as_path(), PortLookup::Found(opt.join("foo"))),
+                (contrib.as_path(), PortLookup::Filtered),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_port_in_repositories_prefers_highest_version_when_requested() {
+        let temp = TestDir::new("ports-prefer-higher");
+        let first_repo = temp.path().join("first");
+        let second_repo = temp.path().join("second");
+        let third_repo = temp.path().join("third");
+        for (repo, version) in [
+            (&first_repo, "1.0"),
+            (&second_repo, "1.2"),
+            (&third_repo, "1.2"),
+        ] {
+            let port = repo.join("foo");
+            fs::create_dir_all(&port).expect("failed to create port directory");
+            write_file(
+                &port.join("Pkgfile"),
+                &format!("version={version}\nrelease=1\n"),
+            );
+        }
+
+        let repo_paths = [
+            repository(&first_repo),
+            repository(&second_repo),
+            repository(&third_repo),
+        ];
+
+        assert_eq!(
+            find_port_in_repositories("foo", &repo_paths, false, Comparison::Libversion),
+            Some(first_repo.join("foo"))
+        );
+        assert_eq!(
+            find_port_in_repositories("foo", &repo_paths, true, Comparison::Libversion),
+            Some(second_repo.join("foo"))
+        );
+    }
+
+    #[test]
+    fn find_port_in_repositories_prefers_highest_version_by_comparison() {
+        let temp = TestDir::new("ports-prefer-higher-comparison");
+        let first_repo = temp.path().join("first");
+        let second_repo = temp.path().join("second");
+        for (repo, version) in [(&first_repo, "1.9"), (&second_repo, "1.10")] {
+            let port = repo.join("foo");
+            fs::create_dir_all(&port).expect("failed to create port directory");
+            write_file(
+                &port.join("Pkgfile"),
+                &format!("version={version}\nrelease=1\n"),
+            );
+        }
+
+        let repo_paths = [repository(&first_repo), repository(&second_repo)];
+
+        assert_eq!(
+            find_port_in_repositories("foo", &repo_paths, true, Comparison::Libversion),
+            Some(second_repo.join("foo"))
+        );
+        assert_eq!(
+            find_port_in_repositories("foo", &repo_paths, true, Comparison::Lexical),
+            Some(first_repo.join("foo"))
+        );
     }
 
     #[test]
@@ -265,7 +762,44 @@ release=4
 ",
         );
 
-        assert_eq!(extract_pkgfile_version(&port), Some("1.2.3-4".to_string()));
+        assert_eq!(
+            extract_pkgfile_version(&port).expect("failed to read Pkgfile"),
+            "1.2.3-4"
+        );
+    }
+
+    #[test]
+    fn extract_pkgfile_version_evaluates_variables() {
+        let temp = TestDir::new("pkgfile-variables");
+        let port = temp.path().join("foo");
+        fs::create_dir_all(&port).expect("failed to create port directory");
+        write_file(
+            &port.join("Pkgfile"),
+            "\
+name=foo
+_major=2
+version=\"${_major}.1\" # comment
+release=1
+",
+        );
+
+        assert_eq!(
+            extract_pkgfile_version(&port).expect("failed to read Pkgfile"),
+            "2.1-1"
+        );
+    }
+
+    #[test]
+    fn extract_pkgfile_version_rejects_mismatching_names() {
+        let temp = TestDir::new("pkgfile-name");
+        let port = temp.path().join("foo");
+        fs::create_dir_all(&port).expect("failed to create port directory");
+        write_file(&port.join("Pkgfile"), "name=bar\nversion=1\nrelease=1\n");
+
+        assert!(matches!(
+            extract_pkgfile_version(&port),
+            Err(PkgfileError::NameMismatch { .. })
+        ));
     }
 
     #[test]
@@ -278,6 +812,9 @@ release=4
 foo
 1.0-1
 usr/bin/foo
+usr/lib/libfoo.so
+usr/lib/libfoo.so.1
+usr/lib/libfoo.so.1.2.0
 usr/share/foo
 
 bar
@@ -289,10 +826,36 @@ usr/bin/bar
         assert_eq!(
             list_installed_packages(db.to_str().expect("test path is not valid utf-8"))
                 .expect("failed to list installed packages"),
-            vec![
-                ("foo".to_string(), Some("1.0-1".to_string())),
-                ("bar".to_string(), Some("2.0-3".to_string()))
-            ]
+            Installed {
+                packages: vec![
+                    ("foo".to_string(), Some("1.0-1".to_string())),
+                    ("bar".to_string(), Some("2.0-3".to_string()))
+                ],
+                libraries: HashMap::from([(
+                    "foo".to_string(),
+                    vec![
+                        "usr/lib/libfoo.so.1".to_string(),
+                        "usr/lib/libfoo.so.1.2.0".to_string()
+                    ]
+                )]),
+            }
+        );
+        assert_eq!(
+            read_installed_files(
+                db.to_str().expect("test path is not valid utf-8"),
+                &HashSet::from(["foo"])
+            )
+            .expect("failed to read installed files"),
+            HashMap::from([(
+                "foo".to_string(),
+                vec![
+                    "usr/bin/foo".to_string(),
+                    "usr/lib/libfoo.so".to_string(),
+                    "usr/lib/libfoo.so.1".to_string(),
+                    "usr/lib/libfoo.so.1.2.0".to_string(),
+                    "usr/share/foo".to_string()
+                ]
+            )])
         );
     }
 }
@@ -0,0 +1,497 @@
+use libversion::{version_compare2, version_compare4, Flags};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Splits a `version-release` string into its upstream version and release.
+pub fn split_release(version: &str) -> (&str, Option<&str>) {
+    match version.rsplit_once('-') {
+        Some((upstream, release)) => (upstream, Some(release)),
+        None => (version, None),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    /// Matches every version starting with the given components, so `6.6`
+    /// matches `6.6`, `6.6.1` and `6.6.30`, but not `6.7` or `6.60`.
+    Prefix,
+}
+
+/// A comma separated list of version requirements that all have to hold,
+/// e.g. `6.6` or `>=6.6,<6.8`. Upstream versions are compared, releases are
+/// ignored. The terms are always compared with libversion, whatever
+/// `--compare` or compare.conf select: a prefix like `6.6` needs version
+/// components, which the other comparisons do not have.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VersionConstraint {
+    terms: Vec<(Operator, String)>,
+}
+
+impl VersionConstraint {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let terms = spec
+            .split(',')
+            .map(|term| {
+                let term = term.trim();
+                let (operator, version) = [
+                    (">=", Operator::GreaterOrEqual),
+                    ("<=", Operator::LessOrEqual),
+                    ("==", Operator::Equal),
+                    (">", Operator::Greater),
+                    ("<", Operator::Less),
+                    ("=", Operator::Equal),
+                ]
+                .into_iter()
+                .find_map(|(prefix, operator)| {
+                    term.strip_prefix(prefix).map(|version| (operator, version))
+                })
+                .unwrap_or((Operator::Prefix, term));
+
+                let version = version.trim();
+                if version.is_empty() {
+                    return Err(format!("missing version in '{term}'"));
+                }
+                Ok((operator, version.to_string()))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self { terms })
+    }
+
+    /// Checks a `version-release` string against every term.
+    pub fn matches(&self, version: &str) -> bool {
+        let (upstream, _) = split_release(version);
+
+        self.terms.iter().all(|(operator, bound)| {
+            let compare = |flags| version_compare4(upstream, bound, Flags::empty(), flags);
+            match operator {
+                Operator::Less => compare(Flags::empty()) == Ordering::Less,
+                Operator::LessOrEqual => compare(Flags::empty()) != Ordering::Greater,
+                Operator::Greater => compare(Flags::empty()) == Ordering::Greater,
+                Operator::GreaterOrEqual => compare(Flags::empty()) != Ordering::Less,
+                Operator::Equal => compare(Flags::empty()) == Ordering::Equal,
+                Operator::Prefix => {
+                    compare(Flags::LowerBound) == Ordering::Greater
+                        && compare(Flags::UpperBound) == Ordering::Less
+                }
+            }
+        })
+    }
+}
+
+/// Whether two `version-release` strings only differ in their release,
+/// according to libversion.
+pub fn is_release_only(installed: &str, available: &str) -> bool {
+    let (installed, _) = split_release(installed);
+    let (available, _) = split_release(available);
+    version_compare4(installed, available, Flags::empty(), Flags::empty()) == Ordering::Equal
+}
+
+/// How far apart two versions are, ordered from the most to the least
+/// significant change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateKind {
+    Major,
+    Minor,
+    Patch,
+    /// Only the CRUX `release` changed, usually a rebuild.
+    Release,
+}
+
+impl UpdateKind {
+    /// Classifies an update by the first upstream version component that
+    /// changed: the first is major, the second minor, any later one a patch.
+    /// Components are compared with libversion, independent of the
+    /// comparison that decided there is an update.
+    pub fn classify(installed: &str, available: &str) -> Self {
+        if is_release_only(installed, available) {
+            return Self::Release;
+        }
+
+        let components = |version| {
+            split_release(version)
+                .0
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .filter(|component| !component.is_empty())
+                .collect::<Vec<_>>()
+        };
+        let (installed, available) = (components(installed), components(available));
+        let changed = (0..installed.len().max(available.len())).find(|&idx| {
+            // A missing component counts as zero, so 1.2 -> 1.2.1 is a patch
+            let installed = installed.get(idx).copied().unwrap_or("0");
+            let available = available.get(idx).copied().unwrap_or("0");
+            version_compare4(installed, available, Flags::empty(), Flags::empty())
+                != Ordering::Equal
+        });
+
+        match changed {
+            Some(0) => Self::Major,
+            Some(1) => Self::Minor,
+            _ => Self::Patch,
+        }
+    }
+}
+
+impl fmt::Display for UpdateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Major => "major",
+            Self::Minor => "minor",
+            Self::Patch => "patch",
+            Self::Release => "release",
+        };
+        f.write_str(kind)
+    }
+}
+
+impl FromStr for UpdateKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "major" => Ok(Self::Major),
+            "minor" => Ok(Self::Minor),
+            "patch" => Ok(Self::Patch),
+            "release" => Ok(Self::Release),
+            _ => Err(format!("unknown update kind '{kind}'")),
+        }
+    }
+}
+
+/// The algorithm used to order a port's version against the installed one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Comparison {
+    #[default]
+    Libversion,
+    /// The comparison `prt-get diff` and `prt-get sysup` use.
+    PrtGet,
+    /// libversion, reading `p` as patch level, so `1.0p1` is newer than `1.0`.
+    PIsPatch,
+    /// libversion, reading any letter suffix as patch level.
+    AnyIsPatch,
+    /// Plain string order, e.g. for zero padded date stamps.
+    Lexical,
+    /// Only the numbers in the versions, ignoring any letters.
+    Numeric,
+    /// Every version that differs from the installed one is an update.
+    TrustPorts,
+}
+
+impl Comparison {
+    /// Orders `a` against `b`, `None` if the versions cannot be ordered.
+    pub fn compare(self, a: &str, b: &str) -> Option<Ordering> {
+        match self {
+            Comparison::Libversion => Some(version_compare2(a, b)),
+            Comparison::PrtGet => prt_get_compare(a, b),
+            Comparison::PIsPatch => Some(compare_with_flags(a, b, Flags::PIsPatch)),
+            Comparison::AnyIsPatch => Some(compare_with_flags(a, b, Flags::AnyIsPatch)),
+            Comparison::Lexical => Some(a.cmp(b)),
+            Comparison::Numeric => Some(compare_numeric(a, b)),
+            Comparison::TrustPorts => Some(if a == b {
+                Ordering::Equal
+            } else {
+                Ordering::Greater
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let comparison = match self {
+            Comparison::Libversion => "libversion",
+            Comparison::PrtGet => "prt-get",
+            Comparison::PIsPatch => "p-is-patch",
+            Comparison::AnyIsPatch => "any-is-patch",
+            Comparison::Lexical => "string",
+            Comparison::Numeric => "numeric",
+            Comparison::TrustPorts => "ports",
+        };
+        f.write_str(comparison)
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(comparison: &str) -> Result<Self, Self::Err> {
+        match comparison {
+            "libversion" => Ok(Comparison::Libversion),
+            "prt-get" => Ok(Comparison::PrtGet),
+            "p-is-patch" => Ok(Comparison::PIsPatch),
+            "any-is-patch" => Ok(Comparison::AnyIsPatch),
+            "string" => Ok(Comparison::Lexical),
+            "numeric" => Ok(Comparison::Numeric),
+            "ports" => Ok(Comparison::TrustPorts),
+            _ => Err(format!("unknown comparison '{comparison}'")),
+        }
+    }
+}
+
+/// Emulates prt-get's version comparison: versions are split into blocks at
+/// `-` and blocks into tokens at `.`. Numeric tokens are compared as
+/// numbers; other tokens are split into runs of digits and letters that are
+/// compared pairwise, numbers as numbers and letters as strings. A digit run
+/// facing a letter run makes the versions unordered. Whenever one side runs
+/// out of runs, tokens or blocks first, the longer side is the greater one,
+/// so unlike libversion `1.0rc1` is newer than `1.0`.
+pub fn prt_get_compare(a: &str, b: &str) -> Option<Ordering> {
+    if a == b {
+        return Some(Ordering::Equal);
+    }
+
+    let blocks_a: Vec<&str> = a.split('-').collect();
+    let blocks_b: Vec<&str> = b.split('-').collect();
+    for (block_a, block_b) in blocks_a.iter().zip(&blocks_b) {
+        let tokens_a: Vec<&str> = block_a.split('.').collect();
+        let tokens_b: Vec<&str> = block_b.split('.').collect();
+        for (token_a, token_b) in tokens_a.iter().zip(&tokens_b) {
+            match compare_prt_get_token(token_a, token_b)? {
+                Ordering::Equal => {}
+                ordering => return Some(ordering),
+            }
+        }
+        if tokens_a.len() != tokens_b.len() {
+            return Some(tokens_a.len().cmp(&tokens_b.len()));
+        }
+    }
+
+    Some(blocks_a.len().cmp(&blocks_b.len()))
+}
+
+fn compare_prt_get_token(a: &str, b: &str) -> Option<Ordering> {
+    let runs_a = digit_and_letter_runs(a);
+    let runs_b = digit_and_letter_runs(b);
+
+    for (run_a, run_b) in runs_a.iter().zip(&runs_b) {
+        let digits = |run: &str| run.bytes().all(|byte| byte.is_ascii_digit());
+        let ordering = match (digits(run_a), digits(run_b)) {
+            (true, true) => compare_numbers(run_a, run_b),
+            (false, false) => run_a.cmp(run_b),
+            _ => return None,
+        };
+        if ordering != Ordering::Equal {
+            return Some(ordering);
+        }
+    }
+
+    Some(runs_a.len().cmp(&runs_b.len()))
+}
+
+/// Splits `1rc2` into `1`, `rc` and `2`.
+fn digit_and_letter_runs(token: &str) -> Vec<&str> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (idx, c) in token.char_indices().skip(1) {
+        let previous = token[..idx].chars().next_back();
+        if previous.is_some_and(|previous| previous.is_ascii_digit() != c.is_ascii_digit()) {
+            runs.push(&token[start..idx]);
+            start = idx;
+        }
+    }
+    if start < token.len() {
+        runs.push(&token[start..]);
+    }
+    runs
+}
+
+/// Compares the upstream versions with `flags`, then the releases. The
+/// release has to be split off, or `1.0p1-1` would face `1.0-1` with `p`
+/// against the release number.
+fn compare_with_flags(a: &str, b: &str, flags: Flags) -> Ordering {
+    let (upstream_a, release_a) = split_release(a);
+    let (upstream_b, release_b) = split_release(b);
+
+    version_compare4(upstream_a, upstream_b, flags, flags).then_with(|| {
+        version_compare2(release_a.unwrap_or_default(), release_b.unwrap_or_default())
+    })
+}
+
+/// Compares the numbers of the upstream versions in order, a missing number
+/// counting as zero, then the releases.
+fn compare_numeric(a: &str, b: &str) -> Ordering {
+    let compare = |a: &str, b: &str| {
+        let numbers = |version: &'_ str| -> Vec<String> {
+            version
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|number| !number.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        let (a, b) = (numbers(a), numbers(b));
+
+        (0..a.len().max(b.len()))
+            .map(|idx| {
+                let number = |numbers: &Vec<String>| numbers.get(idx).cloned().unwrap_or_default();
+                compare_numbers(&number(&a), &number(&b))
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    };
+
+    let (upstream_a, release_a) = split_release(a);
+    let (upstream_b, release_b) = split_release(b);
+    compare(upstream_a, upstream_b)
+        .then_with(|| compare(release_a.unwrap_or_default(), release_b.unwrap_or_default()))
+}
+
+/// Compares digit strings of any length by value.
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(spec: &str) -> VersionConstraint {
+        VersionConstraint::parse(spec).expect("failed to parse constraint")
+    }
+
+    #[test]
+    fn split_release_uses_last_dash() {
+        assert_eq!(split_release("1.2.3-4"), ("1.2.3", Some("4")));
+        assert_eq!(split_release("2024-01-01-1"), ("2024-01-01", Some("1")));
+        assert_eq!(split_release("1.0"), ("1.0", None));
+    }
+
+    #[test]
+    fn prefix_constraint_matches_whole_components() {
+        let lts = constraint("6.6");
+
+        assert!(lts.matches("6.6-1"));
+        assert!(lts.matches("6.6.30-1"));
+        assert!(!lts.matches("6.7-1"));
+        assert!(!lts.matches("6.60-1"));
+        assert!(!lts.matches("6.5.9-1"));
+    }
+
+    #[test]
+    fn range_constraint_requires_every_term() {
+        let range = constraint(">=6.6, <6.8");
+
+        assert!(range.matches("6.6.1-1"));
+        assert!(range.matches("6.7.2-1"));
+        assert!(!range.matches("6.8-1"));
+        assert!(!range.matches("6.5-1"));
+        assert!(constraint("=1.0").matches("1.0-3"));
+        assert!(constraint("<=1.0").matches("1.0-3"));
+        assert!(!constraint(">1.0").matches("1.0-3"));
+    }
+
+    #[test]
+    fn parse_rejects_terms_without_version() {
+        assert!(VersionConstraint::parse(">=").is_err());
+        assert!(VersionConstraint::parse("6.6,").is_err());
+    }
+
+    #[test]
+    fn is_release_only_compares_upstream_versions() {
+        assert!(is_release_only("1.0-1", "1.0-2"));
+        assert!(!is_release_only("1.0-1", "1.1-1"));
+    }
+
+    #[test]
+    fn classify_uses_first_changed_component() {
+        assert_eq!(
+            UpdateKind::classify("1.9.2-1", "2.0.0-1"),
+            UpdateKind::Major
+        );
+        assert_eq!(UpdateKind::classify("6.6.30-1", "6.7-1"), UpdateKind::Minor);
+        assert_eq!(UpdateKind::classify("1.2-1", "1.2.1-1"), UpdateKind::Patch);
+        assert_eq!(
+            UpdateKind::classify("3.1.4a-1", "3.1.4b-1"),
+            UpdateKind::Patch
+        );
+        assert_eq!(UpdateKind::classify("1.0-1", "1.0-2"), UpdateKind::Release);
+        assert_eq!(
+            UpdateKind::classify("20240101-1", "20240201-1"),
+            UpdateKind::Major
+        );
+    }
+
+    #[test]
+    fn update_kind_round_trips_through_strings() {
+        for kind in [
+            UpdateKind::Major,
+            UpdateKind::Minor,
+            UpdateKind::Patch,
+            UpdateKind::Release,
+        ] {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+        assert!("huge".parse::<UpdateKind>().is_err());
+    }
+
+    #[test]
+    fn prt_get_compare_orders_numeric_tokens() {
+        assert_eq!(prt_get_compare("1.10-1", "1.9-1"), Some(Ordering::Greater));
+        assert_eq!(prt_get_compare("1.0-1", "1.0-2"), Some(Ordering::Less));
+        assert_eq!(prt_get_compare("1.0-1", "1.0.1-1"), Some(Ordering::Less));
+        assert_eq!(prt_get_compare("1.02-1", "1.2-1"), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn prt_get_compare_differs_from_libversion_on_suffixes() {
+        assert_eq!(
+            prt_get_compare("1.0rc1-1", "1.0-1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Comparison::Libversion.compare("1.0rc1-1", "1.0-1"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(prt_get_compare("1.0b-1", "1.0a-1"), Some(Ordering::Greater));
+        assert_eq!(prt_get_compare("1.a-1", "1.1-1"), None);
+    }
+
+    #[test]
+    fn digit_and_letter_runs_splits_mixed_tokens() {
+        assert_eq!(digit_and_letter_runs("12rc3"), ["12", "rc", "3"]);
+        assert_eq!(digit_and_letter_runs("p1"), ["p", "1"]);
+        assert!(digit_and_letter_runs("").is_empty());
+    }
+
+    #[test]
+    fn comparison_policies_override_libversion() {
+        assert_eq!(
+            Comparison::PIsPatch.compare("1.0p1-1", "1.0-1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Comparison::Libversion.compare("1.0p1-1", "1.0-1"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            Comparison::Numeric.compare("r20240101-1", "v1.2-1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Comparison::Numeric.compare("1.0-1", "1.0.0-1"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            Comparison::Lexical.compare("2024.01-1", "2023.12-1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Comparison::TrustPorts.compare("1.0-1", "9.9-1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!("ports".parse(), Ok(Comparison::TrustPorts));
+        assert_eq!(
+            Comparison::Lexical.to_string().parse(),
+            Ok(Comparison::Lexical)
+        );
+    }
+}
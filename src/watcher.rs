@@ -0,0 +1,269 @@
+//! A minimal inotify wrapper used by the resident modes to learn when the
+//! package database or a ports collection changed.
+//!
+//! The few libc symbols needed are declared here instead of pulling in a
+//! crate; std already links against libc on Linux.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::raw::{c_char, c_int, c_short, c_ulong};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const IN_CLOEXEC: c_int = 0o2_000_000;
+const IN_NONBLOCK: c_int = 0o4_000;
+
+const IN_MODIFY: u32 = 0x0000_0002;
+const IN_ATTRIB: u32 = 0x0000_0004;
+const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+const IN_MOVED_FROM: u32 = 0x0000_0040;
+const IN_MOVED_TO: u32 = 0x0000_0080;
+const IN_CREATE: u32 = 0x0000_0100;
+const IN_DELETE: u32 = 0x0000_0200;
+const IN_ONLYDIR: u32 = 0x0100_0000;
+const IN_ISDIR: u32 = 0x4000_0000;
+
+const CHANGE_MASK: u32 = IN_MODIFY
+    | IN_ATTRIB
+    | IN_CLOSE_WRITE
+    | IN_MOVED_FROM
+    | IN_MOVED_TO
+    | IN_CREATE
+    | IN_DELETE
+    | IN_ONLYDIR;
+
+const POLLIN: c_short = 0x1;
+const EVENT_HEADER_LEN: usize = 16;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+    fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+}
+
+pub struct Watcher {
+    inotify: File,
+    /// Directories whose new subdirectories get watched too, by watch descriptor.
+    trees: HashMap<c_int, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: inotify_init1 takes no pointers and returns a new fd or -1.
+        let fd = unsafe { inotify_init1(IN_CLOEXEC | IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: fd was just returned by inotify_init1 and is owned by nobody else.
+        let inotify = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        Ok(Self {
+            inotify,
+            trees: HashMap::new(),
+        })
+    }
+
+    /// Watches a single directory for changes of its entries.
+    pub fn watch_dir(&mut self, path: &Path) -> io::Result<c_int> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: path is a valid NUL-terminated string that outlives the call.
+        let wd = unsafe { inotify_add_watch(self.inotify.as_raw_fd(), path.as_ptr(), CHANGE_MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    /// Watches a directory and each of its subdirectories, e.g. a collection
+    /// and its ports. Subdirectories created later are picked up as well.
+    /// A subdirectory that cannot be watched does not stop the others; the
+    /// ones that failed are returned with their errors.
+    pub fn watch_tree(&mut self, path: &Path) -> io::Result<Vec<(PathBuf, io::Error)>> {
+        let wd = self.watch_dir(path)?;
+        self.trees.insert(wd, path.to_path_buf());
+
+        let mut failed = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let watched = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => self.watch_dir(&entry.path()).map(drop),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = watched {
+                failed.push((entry.path(), e));
+            }
+        }
+
+        Ok(failed)
+    }
+
+    /// Blocks until a change was seen and no further change followed within
+    /// `quiet`, or until `timeout` elapsed. Returns whether anything changed.
+    pub fn wait_for_changes(
+        &mut self,
+        timeout: Option<Duration>,
+        quiet: Duration,
+    ) -> io::Result<bool> {
+        if !self.poll(timeout)? {
+            return Ok(false);
+        }
+
+        // Bursts such as `ports -u` are collapsed into a single change
+        self.drain()?;
+        while self.poll(Some(quiet))? {
+            self.drain()?;
+        }
+
+        Ok(true)
+    }
+
+    fn poll(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = timeout.map_or(-1, |timeout| {
+            c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX)
+        });
+        let mut fds = PollFd {
+            fd: self.inotify.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+
+        loop {
+            // SAFETY: fds points to exactly one valid pollfd for the whole call.
+            let ready = unsafe { poll(&mut fds, 1, timeout) };
+            if ready >= 0 {
+                return Ok(ready > 0);
+            }
+
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+
+    fn drain(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let len = match self.inotify.read(&mut buffer) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            for (wd, mask, name) in parse_events(&buffer[..len]) {
+                let new_dir = mask & IN_ISDIR != 0 && mask & (IN_CREATE | IN_MOVED_TO) != 0;
+                if let Some(tree) = self.trees.get(&wd).filter(|_| new_dir) {
+                    let path = tree.join(name);
+                    if let Err(e) = self.watch_dir(&path) {
+                        eprintln!("Failed to watch {}: {e}", path.display());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits a buffer read from inotify into `(wd, mask, name)` triples.
+fn parse_events(buffer: &[u8]) -> Vec<(c_int, u32, String)> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset + EVENT_HEADER_LEN <= buffer.len() {
+        let field = |idx: usize| {
+            let start = offset + idx * 4;
+            [
+                buffer[start],
+                buffer[start + 1],
+                buffer[start + 2],
+                buffer[start + 3],
+            ]
+        };
+        let wd = c_int::from_ne_bytes(field(0));
+        let mask = u32::from_ne_bytes(field(1));
+        let name_len = u32::from_ne_bytes(field(3)) as usize;
+
+        let name_start = offset + EVENT_HEADER_LEN;
+        let name_end = (name_start + name_len).min(buffer.len());
+        let name = buffer[name_start..name_end]
+            .split(|&byte| byte == 0)
+            .next()
+            .unwrap_or_default();
+
+        events.push((wd, mask, String::from_utf8_lossy(name).into_owned()));
+        offset = name_end;
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::tests::TestDir;
+
+    fn event(wd: c_int, mask: u32, name: &str) -> Vec<u8> {
+        let padded_len = name.len().div_ceil(16) * 16;
+        let mut bytes = Vec::new();
+        bytes.extend(wd.to_ne_bytes());
+        bytes.extend(mask.to_ne_bytes());
+        bytes.extend(0u32.to_ne_bytes());
+        bytes.extend((padded_len as u32).to_ne_bytes());
+        bytes.extend(name.as_bytes());
+        bytes.resize(EVENT_HEADER_LEN + padded_len, 0);
+        bytes
+    }
+
+    #[test]
+    fn parse_events_reads_consecutive_events() {
+        let mut buffer = event(1, IN_CREATE | IN_ISDIR, "newport");
+        buffer.extend(event(2, IN_CLOSE_WRITE, ""));
+
+        assert_eq!(
+            parse_events(&buffer),
+            vec![
+                (1, IN_CREATE | IN_ISDIR, "newport".to_string()),
+                (2, IN_CLOSE_WRITE, String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn wait_for_changes_follows_new_port_directories() {
+        let temp = TestDir::new("watch");
+        let collection = temp.path();
+
+        let quiet = Duration::from_millis(50);
+        let mut watcher = Watcher::new().expect("failed to initialize inotify");
+        let failed = watcher
+            .watch_tree(collection)
+            .expect("failed to watch test collection");
+        assert!(failed.is_empty());
+        assert!(!watcher
+            .wait_for_changes(Some(quiet), quiet)
+            .expect("failed to wait"));
+
+        fs::create_dir(collection.join("foo")).expect("failed to create port");
+        assert!(watcher
+            .wait_for_changes(Some(Duration::from_secs(5)), quiet)
+            .expect("failed to wait"));
+
+        fs::write(collection.join("foo/Pkgfile"), "version=1\n").expect("failed to write Pkgfile");
+        assert!(watcher
+            .wait_for_changes(Some(Duration::from_secs(5)), quiet)
+            .expect("failed to wait"));
+    }
+}